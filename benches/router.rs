@@ -0,0 +1,129 @@
+// cargo bench --bench router
+// compares how the server routes a request (the prefix tree, params included) to the
+// linear scan it used to do
+use std::{collections::HashMap, hint::black_box, time::Instant};
+
+use cf::{meta::Method, server::Server};
+
+// the old matcher, kept here so there's something to compare against
+enum RouteSegment {
+    Static(String),
+    Param(String),
+}
+
+struct Route {
+    segments: Vec<RouteSegment>,
+    value: usize,
+}
+
+fn linear_route(path: &str, value: usize) -> Route {
+    let segments = path
+        .trim_start_matches('/')
+        .split('/')
+        .map(|s| {
+            if s.starts_with('{') && s.ends_with('}') {
+                RouteSegment::Param(s[1..s.len() - 1].to_string())
+            } else {
+                RouteSegment::Static(s.to_string())
+            }
+        })
+        .collect();
+    Route { segments, value }
+}
+
+fn linear_match<'a>(
+    routes: &'a [Route],
+    path: &str,
+) -> Option<(&'a usize, HashMap<String, String>)> {
+    let req_segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+
+    for route in routes {
+        if route.segments.len() != req_segments.len() {
+            continue;
+        }
+
+        let mut params = HashMap::new();
+        let mut matched = true;
+
+        for (seg, req_seg) in route.segments.iter().zip(req_segments.iter()) {
+            match seg {
+                RouteSegment::Static(s) if s != (*req_seg) => {
+                    matched = false;
+                    break;
+                }
+                RouteSegment::Param(name) => {
+                    params.insert(name.clone(), (*req_seg).to_string());
+                }
+                RouteSegment::Static(_) => {}
+            }
+        }
+
+        if matched {
+            return Some((&route.value, params));
+        }
+    }
+
+    None
+}
+
+fn patterns(n: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    for i in 0..n / 4 {
+        out.push(format!("/api/v1/resource{i}"));
+        out.push(format!("/api/v1/resource{i}/{{id}}"));
+        out.push(format!("/api/v1/resource{i}/{{id}}/items/{{item}}"));
+        out.push(format!("/static/page{i}/about"));
+    }
+    out
+}
+
+fn bench(name: &str, iters: u32, mut f: impl FnMut()) {
+    let start = Instant::now();
+    for _ in 0..iters {
+        f();
+    }
+    let per = start.elapsed() / iters;
+    println!("{name:<48} {per:>10?}/iter");
+}
+
+fn main() {
+    const ITERS: u32 = 100_000;
+
+    for n in [8, 100, 400] {
+        let patterns = patterns(n);
+
+        let linear: Vec<Route> = patterns
+            .iter()
+            .enumerate()
+            .map(|(i, p)| linear_route(p, i))
+            .collect();
+        let mut server = Server::new("127.0.0.1:0");
+        for p in &patterns {
+            server = server.get(p, |_req| "");
+        }
+        let mut route = server.bench_routing();
+
+        let last = n / 4 - 1;
+        let first_hit = "/api/v1/resource0/42".to_string();
+        let last_hit = format!("/api/v1/resource{last}/42/items/7");
+        let miss = "/api/v2/nothing/here".to_string();
+
+        println!("{n} routes");
+        for (label, path) in [
+            ("first route", &first_hit),
+            ("last route", &last_hit),
+            ("no match", &miss),
+        ] {
+            assert_eq!(
+                linear_match(&linear, path).map(|(_, params)| params),
+                route(Method::GET, path),
+            );
+            bench(&format!("  linear, {label}"), ITERS, || {
+                black_box(linear_match(&linear, black_box(path)));
+            });
+            bench(&format!("  server, {label}"), ITERS, || {
+                black_box(route(Method::GET, black_box(path)));
+            });
+        }
+    }
+}
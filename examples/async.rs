@@ -0,0 +1,19 @@
+use cf::{middleware, request::Request, response::Response, server::Server};
+use smol::Timer;
+use std::time::Duration;
+
+fn main() -> std::io::Result<()> {
+    Server::at("127.0.0.1:8080")
+        .mw(middleware::logger)
+        .mw(middleware::from_fn(|req, next| async move {
+            // pretend we're asking a database whether this ip is allowed in
+            Timer::after(Duration::from_millis(10)).await;
+            next.run(&req).await.header("X-Checked", "yes")
+        }))
+        .get("/sync", |_req| "still works")
+        .get_async("/slow", |req: Request| async move {
+            Timer::after(Duration::from_millis(500)).await;
+            Response::text(format!("sorry for the wait, {}", req.peer_addr))
+        })
+        .serve()
+}
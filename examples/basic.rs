@@ -23,6 +23,10 @@ fn main() -> std::io::Result<()> {
                 req.param("name").unwrap()
             ))
         })
+        // only numbers get here, /user/bob still goes to the one above
+        .get("/user/{id:u64}", |req: &Request| {
+            format!("user #{}", req.param_as::<u64>("id").unwrap())
+        })
         .static_route("/static", "./examples/static")
         .serve()
 }
@@ -1,13 +1,36 @@
-use cf::{meta::Handler, request::Request, response::Response, server::Server};
+use cf::{
+    extract::{self, Ctx},
+    meta::Handler,
+    middleware::{self, Next},
+    request::Request,
+    response::Response,
+    server::Server,
+};
+
+#[derive(Clone)]
+struct User {
+    name: String,
+    admin: bool,
+}
 
 fn main() {
     Server::at("127.0.0.1:8080")
         .mw(check_auth)
+        .mw(middleware::from_fn(load_user))
         .get("/hi", |req| {
             // middleware has checked auto and stored the user in context
             let name: String = req.context.get("name").unwrap_or("guest".to_string());
             Response::text(format!("hello, {}", name))
         })
+        .get("/me", |req| match req.context.value::<User>() {
+            Some(user) => Response::text(format!("{} (admin: {})", user.name, user.admin)),
+            None => Response::text("who are you"),
+        })
+        // or let an extractor do it
+        .get(
+            "/admin",
+            extract::with(|Ctx(user): Ctx<User>| format!("admin? {}", user.admin)),
+        )
         .serve()
         .unwrap();
 }
@@ -21,3 +44,12 @@ fn check_auth(handler: Handler) -> Handler {
         handler(req)
     })
 }
+
+// from_fn hands you your own request, so typed values can go in without a lock
+async fn load_user(mut req: Request, next: Next) -> Response {
+    if let Some(name) = req.context.get("name") {
+        let admin = name == "root";
+        req.context.insert(User { name, admin });
+    }
+    next.run(&req).await
+}
@@ -0,0 +1,35 @@
+use cf::{
+    error::{Error, Result},
+    meta::StatusCode,
+    request::Request,
+    response::Response,
+    server::Server,
+};
+
+fn main() -> std::io::Result<()> {
+    Server::at("127.0.0.1:8080")
+        // every error that comes out of a handler goes through here
+        .on_error(|err, req| {
+            Response::json(format!(
+                r#"{{"error": {:?}, "path": {:?}}}"#,
+                err.message, req.route
+            ))
+            .status(err.status)
+        })
+        // /double?n=21 is fine, /double?n=abc is a 400 thanks to the ?
+        .get("/double", |req: &Request| -> Result<String> {
+            let n: i64 = req
+                .query_param("n")
+                .ok_or(Error::bad_request("missing n"))?
+                .parse()?;
+            Ok(format!("{}", n * 2))
+        })
+        .get_async("/file", |_req| async {
+            let contents = smol::fs::read_to_string("./does/not/exist").await?;
+            Ok::<_, Error>(contents)
+        })
+        .get("/teapot", |_req| -> Result<&str> {
+            Err(StatusCode::ImATeapot.into())
+        })
+        .serve()
+}
@@ -0,0 +1,53 @@
+use std::net::SocketAddr;
+
+use cf::{
+    extract::{self, Form, Path, Query, TypedHeader, UserAgent},
+    server::Server,
+};
+
+struct Filter {
+    page: u32,
+    tag: Option<String>,
+}
+cf::from_params!(Filter { page, tag });
+
+struct Login {
+    user: String,
+    password: String,
+}
+cf::from_params!(Login { user, password });
+
+fn main() -> std::io::Result<()> {
+    Server::at("127.0.0.1:8080")
+        // /user/12 works, /user/bob is a 400
+        .get(
+            "/user/{id}",
+            extract::with(|Path(id): Path<u64>| format!("user #{id}")),
+        )
+        // /posts?page=2&tag=rust
+        .get(
+            "/posts",
+            extract::with(|Query(filter): Query<Filter>, addr: SocketAddr| {
+                format!(
+                    "page {} of posts tagged {} for {addr}",
+                    filter.page,
+                    filter.tag.as_deref().unwrap_or("anything")
+                )
+            }),
+        )
+        .post(
+            "/login",
+            extract::with(|Form(login): Form<Login>| {
+                format!("hi {}, {} chars", login.user, login.password.len())
+            }),
+        )
+        .get_async(
+            "/ua",
+            extract::with_async(
+                |TypedHeader(UserAgent(ua)): TypedHeader<UserAgent>| async move {
+                    format!("ur using {ua}")
+                },
+            ),
+        )
+        .serve()
+}
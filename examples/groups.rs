@@ -0,0 +1,67 @@
+use cf::{
+    meta::{Handler, StatusCode},
+    middleware,
+    request::Request,
+    response::Response,
+    server::Server,
+};
+
+fn main() -> std::io::Result<()> {
+    Server::at("127.0.0.1:8080")
+        .mw(middleware::logger) // still wraps everything, 404s included
+        .get("/", |_req| "public")
+        // one route with its own middleware, without a group
+        .get("/secret", |_req| "the cake is a lie")
+        .layer(json_errors)
+        .layer(admin_only)
+        .group("/api", |g| {
+            // the first one added is the outermost, so json_errors sees auth's 401s
+            g.mw(json_errors)
+                .mw(auth)
+                .get("/me", |req: &Request| {
+                    Response::json(format!(r#"{{"token": {:?}}}"#, req.header("authorization")))
+                })
+                // /api/admin/stats needs both the api and the admin middleware
+                .group("/admin", |g| {
+                    g.mw(admin_only)
+                        .get("/stats", |_req| Response::json(r#"{"users": 3}"#))
+                })
+        })
+        .static_route("/static", "./examples/static")
+        .serve()
+}
+
+fn auth(handler: Handler) -> Handler {
+    Box::new(move |req: &Request| {
+        if req.header("authorization").is_none() {
+            return Box::pin(std::future::ready(Response::error(
+                StatusCode::Unauthorized,
+            )));
+        }
+        handler(req)
+    })
+}
+
+fn admin_only(handler: Handler) -> Handler {
+    Box::new(move |req: &Request| {
+        if req.header("authorization") != Some("admin") {
+            return Box::pin(std::future::ready(Response::error(StatusCode::Forbidden)));
+        }
+        handler(req)
+    })
+}
+
+fn json_errors(handler: Handler) -> Handler {
+    Box::new(move |req: &Request| {
+        let res = handler(req);
+        Box::pin(async move {
+            let res = res.await;
+            if res.status as u16 >= 400 {
+                let status = res.status;
+                Response::json(format!(r#"{{"error": {:?}}}"#, status.as_str())).status(status)
+            } else {
+                res
+            }
+        })
+    })
+}
@@ -0,0 +1,33 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use cf::{
+    extract::{self, State},
+    server::Server,
+};
+
+struct Config {
+    greeting: String,
+}
+
+#[derive(Default)]
+struct Hits(AtomicUsize);
+
+fn main() -> std::io::Result<()> {
+    Server::at("127.0.0.1:8080")
+        .state(Config {
+            greeting: "hiiii".into(),
+        })
+        .state(Hits::default())
+        .get("/", |req| {
+            let config = req.state.get::<Config>().unwrap();
+            format!("{} from the config", config.greeting)
+        })
+        .get(
+            "/hits",
+            extract::with(|State(hits): State<Hits>| {
+                let n = hits.0.fetch_add(1, Ordering::Relaxed) + 1;
+                format!("{n} hits so far")
+            }),
+        )
+        .serve()
+}
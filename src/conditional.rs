@@ -0,0 +1,70 @@
+// conditional GETs, so a client that already has the file gets an empty 304 instead of
+// downloading it again. see Response::conditional
+
+use std::time::SystemTime;
+
+use crate::{
+    meta::{Method, StatusCode, http_date, parse_http_date},
+    request::Request,
+    response::Response,
+};
+
+// W/"<size>-<mtime>". weak because it doesn't look at the contents, two files with the
+// same size and mtime would get the same one
+pub fn weak_etag(len: u64, modified: SystemTime) -> String {
+    let mtime = modified
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    format!("W/\"{len:x}-{mtime:x}\"")
+}
+
+// the ETag and Last-Modified a file response gets
+pub(crate) fn file_validators(len: u64, modified: SystemTime) -> [(&'static str, String); 2] {
+    [
+        ("ETag", weak_etag(len, modified)),
+        ("Last-Modified", http_date(modified)),
+    ]
+}
+
+pub(crate) fn apply(res: Response, req: &Request) -> Response {
+    if res.status != StatusCode::Ok || !matches!(req.method, Method::GET | Method::HEAD) {
+        return res;
+    }
+    let header = |name: &str| {
+        res.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    };
+
+    // If-None-Match wins, If-Modified-Since is only for clients that don't do etags
+    let not_modified = match req.header("if-none-match") {
+        Some(tags) => header("etag").is_some_and(|etag| etag_matches(tags, etag)),
+        None => match (req.header("if-modified-since"), header("last-modified")) {
+            (Some(since), Some(modified)) => parse_http_date(since)
+                .zip(parse_http_date(modified))
+                .is_some_and(|(since, modified)| modified <= since),
+            _ => false,
+        },
+    };
+    if !not_modified {
+        return res;
+    }
+
+    // a 304 keeps the headers that describe the cached copy, but has no body
+    let mut not_modified = Response::empty().status(StatusCode::NotModified);
+    for name in ["ETag", "Last-Modified", "Cache-Control", "Expires", "Vary"] {
+        if let Some(value) = header(name) {
+            not_modified = not_modified.header(name, value);
+        }
+    }
+    not_modified
+}
+
+// If-None-Match compares weakly, so W/"a" matches "a"
+fn etag_matches(tags: &str, etag: &str) -> bool {
+    let strip = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = strip(etag);
+    tags.split(',')
+        .any(|tag| tag.trim() == "*" || strip(tag) == etag)
+}
@@ -0,0 +1,441 @@
+// what a {param:...} is allowed to match. either a type name like {id:u64} or {n:int},
+// or a small regex like {slug:[a-z0-9-]+}. the regex always has to match the whole segment
+// and only knows chars, ., [classes], \d \w \s and the ? * + {n,m} quantifiers.
+// two constraints are the same if they accept the same things the same way, so u64 and
+// uint are, and so are [0-9]+ and \d+
+#[derive(Debug, Clone)]
+pub struct Constraint {
+    source: String,
+    kind: Kind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    Int { min: i128, max: i128 },
+    Uint { max: u128 },
+    Float,
+    Bool,
+    Pattern(Vec<Piece>),
+}
+
+// the value a typed param parsed to while it was being matched
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamValue {
+    Int(i128),
+    Uint(u128),
+    Float(f64),
+    Bool(bool),
+}
+
+impl PartialEq for Constraint {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+impl Eq for Constraint {}
+
+impl std::fmt::Display for Constraint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl Constraint {
+    // panics on a regex it doesn't understand, routes are set up once at startup anyways
+    pub fn parse(source: &str) -> Self {
+        let kind = match source {
+            "int" | "i64" => Kind::Int {
+                min: i64::MIN.into(),
+                max: i64::MAX.into(),
+            },
+            "uint" | "u64" => Kind::Uint {
+                max: u64::MAX.into(),
+            },
+            "i8" => int_kind(i8::MIN.into(), i8::MAX.into()),
+            "i16" => int_kind(i16::MIN.into(), i16::MAX.into()),
+            "i32" => int_kind(i32::MIN.into(), i32::MAX.into()),
+            "i128" => int_kind(i128::MIN, i128::MAX),
+            "isize" => int_kind(isize::MIN as i128, isize::MAX as i128),
+            "u8" => uint_kind(u8::MAX.into()),
+            "u16" => uint_kind(u16::MAX.into()),
+            "u32" => uint_kind(u32::MAX.into()),
+            "u128" => uint_kind(u128::MAX),
+            "usize" => uint_kind(usize::MAX as u128),
+            "float" | "f32" | "f64" => Kind::Float,
+            "bool" => Kind::Bool,
+            _ => Kind::Pattern(parse_regex(source)),
+        };
+        Self {
+            source: source.to_string(),
+            kind,
+        }
+    }
+
+    // which one gets tried first when different constraints are at the same spot, lowest
+    // first: bool, then ints from the narrowest to the widest (unsigned first if they're
+    // as wide), floats, and regexes last, by their source since there's no telling which
+    // is narrower. so it never depends on the order routes were added in
+    pub(crate) fn priority(&self) -> (u8, u128, bool, &str) {
+        match &self.kind {
+            Kind::Bool => (0, 0, false, ""),
+            Kind::Uint { max } => (1, *max, false, ""),
+            Kind::Int { min, max } => (1, max.abs_diff(*min), true, ""),
+            Kind::Float => (2, 0, false, ""),
+            Kind::Pattern(_) => (3, 0, false, &self.source),
+        }
+    }
+
+    // None if the segment doesn't fit, otherwise whatever it parsed to (if it's typed)
+    pub fn check(&self, segment: &str) -> Option<Option<ParamValue>> {
+        match &self.kind {
+            Kind::Int { min, max } => {
+                let n: i128 = segment.parse().ok()?;
+                (*min..=*max)
+                    .contains(&n)
+                    .then_some(Some(ParamValue::Int(n)))
+            }
+            Kind::Uint { max } => {
+                let n: u128 = segment.parse().ok()?;
+                (n <= *max).then_some(Some(ParamValue::Uint(n)))
+            }
+            Kind::Float => {
+                let n: f64 = segment.parse().ok()?;
+                n.is_finite().then_some(Some(ParamValue::Float(n)))
+            }
+            Kind::Bool => Some(Some(ParamValue::Bool(segment.parse().ok()?))),
+            Kind::Pattern(pieces) => matches(pieces, segment).then_some(None),
+        }
+    }
+}
+
+fn int_kind(min: i128, max: i128) -> Kind {
+    Kind::Int { min, max }
+}
+
+fn uint_kind(max: u128) -> Kind {
+    Kind::Uint { max }
+}
+
+// lets req.param_as::<T>() hand back the value the router already parsed
+pub trait FromParamValue: Sized {
+    fn from_param_value(value: ParamValue) -> Option<Self>;
+}
+
+macro_rules! from_param_value_int {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl FromParamValue for $t {
+                fn from_param_value(value: ParamValue) -> Option<Self> {
+                    match value {
+                        ParamValue::Int(n) => n.try_into().ok(),
+                        ParamValue::Uint(n) => n.try_into().ok(),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+from_param_value_int!(
+    i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize
+);
+
+impl FromParamValue for f64 {
+    fn from_param_value(value: ParamValue) -> Option<Self> {
+        match value {
+            ParamValue::Float(n) => Some(n),
+            ParamValue::Int(n) => Some(n as f64),
+            ParamValue::Uint(n) => Some(n as f64),
+            ParamValue::Bool(_) => None,
+        }
+    }
+}
+
+impl FromParamValue for f32 {
+    fn from_param_value(value: ParamValue) -> Option<Self> {
+        f64::from_param_value(value).map(|n| n as f32)
+    }
+}
+
+impl FromParamValue for bool {
+    fn from_param_value(value: ParamValue) -> Option<Self> {
+        match value {
+            ParamValue::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+}
+
+//
+// the tiny regex
+//
+
+#[derive(Debug, Clone, PartialEq)]
+struct Piece {
+    atom: Atom,
+    min: usize,
+    max: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Atom {
+    Any,
+    Char(char),
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Atom {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Atom::Any => true,
+            Atom::Char(want) => c == *want,
+            Atom::Class { negated, ranges } => {
+                ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&c)) != *negated
+            }
+        }
+    }
+}
+
+fn escape_class(c: char) -> Option<Atom> {
+    let (negated, ranges) = match c {
+        'd' => (false, vec![('0', '9')]),
+        'D' => (true, vec![('0', '9')]),
+        'w' => (false, vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')]),
+        'W' => (true, vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')]),
+        's' => (false, vec![(' ', ' '), ('\t', '\t')]),
+        'S' => (true, vec![(' ', ' '), ('\t', '\t')]),
+        _ => return None,
+    };
+    Some(Atom::Class { negated, ranges })
+}
+
+fn parse_regex(source: &str) -> Vec<Piece> {
+    let unsupported = |what: &str| -> ! {
+        panic!("unsupported {what} in route constraint {source:?}");
+    };
+    // anchors are implied, so allow them but ignore them
+    let pattern = source.strip_prefix('^').unwrap_or(source);
+    let pattern = pattern.strip_suffix('$').unwrap_or(pattern);
+
+    let mut pieces: Vec<Piece> = Vec::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        let atom = match c {
+            '.' => Atom::Any,
+            '\\' => {
+                let Some(e) = chars.next() else {
+                    unsupported("trailing \\")
+                };
+                escape_class(e).unwrap_or(Atom::Char(e))
+            }
+            '[' => {
+                let negated = chars.next_if_eq(&'^').is_some();
+                let mut ranges = Vec::new();
+                loop {
+                    let lo = match chars.next() {
+                        None => unsupported("unclosed ["),
+                        Some(']') if !ranges.is_empty() => break,
+                        Some('\\') => {
+                            let Some(e) = chars.next() else {
+                                unsupported("trailing \\")
+                            };
+                            if let Some(Atom::Class {
+                                negated: false,
+                                ranges: r,
+                            }) = escape_class(e)
+                            {
+                                ranges.extend(r);
+                                continue;
+                            }
+                            e
+                        }
+                        Some(lo) => lo,
+                    };
+                    // a - right before the ] is just a -
+                    if chars.peek() == Some(&'-') {
+                        chars.next();
+                        match chars.next() {
+                            Some(']') => {
+                                ranges.push((lo, lo));
+                                ranges.push(('-', '-'));
+                                break;
+                            }
+                            Some(hi) => ranges.push((lo, hi)),
+                            None => unsupported("unclosed ["),
+                        }
+                    } else {
+                        ranges.push((lo, lo));
+                    }
+                }
+                Atom::Class { negated, ranges }
+            }
+            '?' | '*' | '+' | '{' => unsupported("quantifier with nothing before it"),
+            '(' | ')' | '|' => unsupported("group or alternation"),
+            c => Atom::Char(c),
+        };
+
+        let (min, max) = if chars.next_if_eq(&'?').is_some() {
+            (0, 1)
+        } else if chars.next_if_eq(&'*').is_some() {
+            (0, usize::MAX)
+        } else if chars.next_if_eq(&'+').is_some() {
+            (1, usize::MAX)
+        } else if chars.next_if_eq(&'{').is_some() {
+            let inner: String = chars.by_ref().take_while(|c| *c != '}').collect();
+            let parse = |s: &str| {
+                s.trim()
+                    .parse::<usize>()
+                    .unwrap_or_else(|_| unsupported("{n,m} quantifier"))
+            };
+            match inner.split_once(',') {
+                None => (parse(&inner), parse(&inner)),
+                Some((lo, "")) => (parse(lo), usize::MAX),
+                Some((lo, hi)) => (parse(lo), parse(hi)),
+            }
+        } else {
+            (1, 1)
+        };
+        pieces.push(Piece { atom, min, max });
+    }
+    pieces
+}
+
+// backtracking, but segments are short so it doesn't matter
+fn matches(pieces: &[Piece], s: &str) -> bool {
+    let Some((piece, rest)) = pieces.split_first() else {
+        return s.is_empty();
+    };
+    // greedy: see how many chars it could take, then give them back one at a time
+    let most = s
+        .chars()
+        .take_while(|c| piece.atom.matches(*c))
+        .take(piece.max)
+        .count();
+    (piece.min..=most).rev().any(|taken| {
+        let end = s.char_indices().nth(taken).map_or(s.len(), |(i, _)| i);
+        matches(rest, &s[end..])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aliases_are_equal() {
+        let eq = |a: &str, b: &str| Constraint::parse(a) == Constraint::parse(b);
+        assert!(eq("u64", "uint"));
+        assert!(eq("int", "i64"));
+        assert!(eq("f32", "float"));
+        assert!(eq("[0-9]+", "\\d+"));
+        assert!(!eq("u64", "u32"));
+        assert!(!eq("i8", "u8"));
+        assert!(!eq("[0-9]+", "[0-9]*"));
+    }
+
+    fn regex(source: &str, s: &str) -> bool {
+        matches(&parse_regex(source), s)
+    }
+
+    #[test]
+    fn classes_and_quantifiers() {
+        assert!(regex("[a-z0-9-]+", "hello-world-2"));
+        assert!(!regex("[a-z0-9-]+", "Hello"));
+        assert!(!regex("[a-z0-9-]+", ""));
+        assert!(regex("[^/]+", "a.b"));
+        assert!(!regex("[^/]+", "a/b"));
+        assert!(regex(r"[\d_]+", "1_000"));
+        assert!(regex("colou?r", "color"));
+        assert!(regex("colou?r", "colour"));
+        assert!(regex("a*b", "b"));
+        assert!(regex("a*b", "aaab"));
+    }
+
+    #[test]
+    fn trailing_dash_in_a_class() {
+        assert!(regex("[a-]", "a"));
+        assert!(regex("[a-]", "-"));
+        assert!(!regex("[a-]", "b"));
+    }
+
+    #[test]
+    fn counted_repeats() {
+        assert!(regex(r"\d{4}", "2024"));
+        assert!(!regex(r"\d{4}", "202"));
+        assert!(!regex(r"\d{4}", "20245"));
+        assert!(!regex(r"\d{4}", "abcd"));
+        assert!(regex(r"\w{2,}", "ab"));
+        assert!(!regex(r"\w{2,}", "a"));
+        assert!(regex("x{1,3}", "xxx"));
+        assert!(!regex("x{1,3}", "xxxx"));
+    }
+
+    #[test]
+    fn whole_segment_and_backtracking() {
+        assert!(regex(r".*\.txt", "a.b.txt"));
+        assert!(!regex(r".*\.txt", "a.txt.gz"));
+        // anchors are allowed but the match is always anchored anyways
+        assert!(regex(r"^\w+$", "abc_1"));
+        assert!(!regex("abc", "abcd"));
+        assert!(regex("é.", "éü"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_groups() {
+        parse_regex("(a|b)");
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_unclosed_classes() {
+        parse_regex("[abc");
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_dangling_quantifiers() {
+        parse_regex("+a");
+    }
+
+    #[test]
+    fn integer_ranges() {
+        let u8 = Constraint::parse("u8");
+        assert_eq!(u8.check("255"), Some(Some(ParamValue::Uint(255))));
+        assert_eq!(u8.check("256"), None);
+        assert_eq!(u8.check("-1"), None);
+
+        let i64 = Constraint::parse("i64");
+        let max = i64::MAX.to_string();
+        let min = i64::MIN.to_string();
+        assert_eq!(
+            i64.check(&max),
+            Some(Some(ParamValue::Int(i64::MAX.into())))
+        );
+        assert_eq!(
+            i64.check(&min),
+            Some(Some(ParamValue::Int(i64::MIN.into())))
+        );
+        assert_eq!(i64.check("9223372036854775808"), None);
+        assert_eq!(i64.check("-9223372036854775809"), None);
+        assert_eq!(i64.check("12a"), None);
+
+        assert_eq!(Constraint::parse("i8").check("-129"), None);
+    }
+
+    #[test]
+    fn floats_and_bools() {
+        let float = Constraint::parse("f64");
+        assert_eq!(float.check("1.5"), Some(Some(ParamValue::Float(1.5))));
+        assert_eq!(float.check("inf"), None);
+        assert_eq!(float.check("NaN"), None);
+        let bool = Constraint::parse("bool");
+        assert_eq!(bool.check("true"), Some(Some(ParamValue::Bool(true))));
+        assert_eq!(bool.check("yes"), None);
+    }
+}
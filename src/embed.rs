@@ -0,0 +1,178 @@
+// static files baked into the binary, for when there's no ./public next to it.
+// a build script writes the list of files with `generate`, and `include_assets!` pulls it in:
+//
+// // build.rs, with cf in [build-dependencies]
+// fn main() {
+//     cf::embed::generate("public", "assets.rs").unwrap();
+// }
+//
+// // main.rs
+// static ASSETS: Embedded = cf::include_assets!("assets.rs");
+// Server::at("127.0.0.1:8080").embedded_route("/static", ASSETS)
+
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    error::Error,
+    files::file_path,
+    meta::{self, Handler, StatusCode, guess_content_type, http_date},
+    request::Request,
+    response::{IntoResponse, Response},
+};
+
+#[derive(Clone, Copy)]
+pub struct Embedded {
+    // sorted by path
+    files: &'static [EmbeddedFile],
+}
+
+// the fields are pub so the generated code can build these, you shouldn't have to
+pub struct EmbeddedFile {
+    // relative to the embedded dir, with / between the pieces
+    pub path: &'static str,
+    pub contents: &'static [u8],
+    // of the contents, it's what the ETag is made from
+    pub hash: u64,
+    // seconds since the epoch, 0 if the file system didn't say
+    pub modified: u64,
+}
+
+// the `EmbeddedFile`s `generate` wrote, as an `Embedded`
+#[macro_export]
+macro_rules! include_assets {
+    ($file:literal) => {{
+        use $crate::embed::{Embedded, EmbeddedFile};
+        Embedded::new(include!(concat!(env!("OUT_DIR"), "/", $file)))
+    }};
+}
+
+impl Embedded {
+    // the files have to be sorted by path, `generate` does that
+    pub const fn new(files: &'static [EmbeddedFile]) -> Self {
+        Self { files }
+    }
+
+    // ASSETS.get("css/site.css")
+    pub fn get(&self, path: &str) -> Option<&'static EmbeddedFile> {
+        let files = self.files;
+        files
+            .binary_search_by(|file| file.path.cmp(path))
+            .ok()
+            .map(|i| &files[i])
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &'static EmbeddedFile> {
+        self.files.iter()
+    }
+
+    pub(crate) fn into_handler(self) -> Handler {
+        meta::handler(move |req| {
+            let res = match file_path(req) {
+                Ok(parts) => self.response(&parts.join("/"), req),
+                Err(status) => Error::from(status).into_response(),
+            };
+            Box::pin(std::future::ready(res))
+        })
+    }
+
+    // the file, or the index.html of the dir, like static_route
+    fn response(&self, path: &str, req: &Request) -> Response {
+        let index = if path.is_empty() {
+            "index.html".to_string()
+        } else {
+            format!("{path}/index.html")
+        };
+        let Some(file) = self.get(path).or_else(|| self.get(&index)) else {
+            return Error::from(StatusCode::NotFound).into_response();
+        };
+
+        // a strong etag this time, the hash is of the contents
+        let mut res =
+            Response::static_bytes(file.contents, guess_content_type(Path::new(file.path)))
+                .header("ETag", format!("\"{:016x}\"", file.hash));
+        if file.modified > 0 {
+            let modified = UNIX_EPOCH + Duration::from_secs(file.modified);
+            res = res.header("Last-Modified", http_date(modified));
+        }
+        res.conditional(req).range(req)
+    }
+}
+
+// for build scripts: writes every file under `dir` (relative to the crate's Cargo.toml) to
+// `$OUT_DIR/<out>`, for include_assets!(out) to embed. cargo reruns the build script when
+// anything in the dir changes
+pub fn generate(dir: impl AsRef<Path>, out: &str) -> std::io::Result<()> {
+    let out_dir = std::env::var_os("OUT_DIR").ok_or_else(|| {
+        std::io::Error::other("OUT_DIR isn't set, generate() has to run in a build script")
+    })?;
+    let dir = dir.as_ref().canonicalize()?;
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let mut files = Vec::new();
+    walk(&dir, &mut files)?;
+    let mut files = files
+        .into_iter()
+        .map(|path| {
+            let relative = path
+                .strip_prefix(&dir)
+                .ok()
+                .and_then(|relative| {
+                    let parts: Option<Vec<&str>> = relative
+                        .components()
+                        .map(|c| c.as_os_str().to_str())
+                        .collect();
+                    parts.map(|parts| parts.join("/"))
+                })
+                .ok_or_else(|| not_utf8(&path))?;
+            Ok((relative, path))
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
+    files.sort();
+
+    let mut code = String::from("&[\n");
+    for (relative, path) in files {
+        let contents = std::fs::read(&path)?;
+        let modified = std::fs::metadata(&path)?
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs());
+        let absolute = path.to_str().ok_or_else(|| not_utf8(&path))?;
+        writeln!(
+            code,
+            "    EmbeddedFile {{ path: {relative:?}, contents: include_bytes!({absolute:?}), hash: {:#x}, modified: {modified} }},",
+            fnv1a(&contents)
+        )
+        .unwrap();
+    }
+    code.push(']');
+    std::fs::write(Path::new(&out_dir).join(out), code)
+}
+
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let metadata = std::fs::metadata(&path)?;
+        if metadata.is_dir() {
+            walk(&path, files)?;
+        } else if metadata.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn not_utf8(path: &Path) -> std::io::Error {
+    std::io::Error::other(format!("{} isn't valid utf-8", path.display()))
+}
+
+// doesn't need to be cryptographic, just change when the contents do
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,181 @@
+use std::{
+    fmt::Display,
+    num::{ParseFloatError, ParseIntError},
+    panic::{AssertUnwindSafe, catch_unwind},
+    str::{ParseBoolError, Utf8Error},
+    string::FromUtf8Error,
+    sync::Arc,
+};
+
+use smol::future::FutureExt;
+
+use crate::{
+    meta::{Handler, StatusCode},
+    request::Request,
+    response::{IntoResponse, Response},
+    router::UrlError,
+};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+// renders errors for the whole server, see Server::on_error
+pub type ErrorHandler = Box<dyn Fn(&Error, &Request) -> Response + Send + Sync>;
+
+// an error a handler can bubble up with ?, it turns into a response with its status
+#[derive(Debug, Clone)]
+pub struct Error {
+    pub status: StatusCode,
+    pub message: String,
+    // what caused it, kept out of the message since that's what the client sees. the
+    // server logs it if the error ends in a 500
+    source: Option<Arc<dyn std::error::Error + Send + Sync>>,
+}
+
+impl Error {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BadRequest, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NotFound, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::InternalServerError, message)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.message.is_empty() {
+            write!(f, "{}", self.status.as_str())
+        } else {
+            write!(f, "{}: {}", self.status.as_str(), self.message)
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.as_deref().map(|source| source as _)
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let mut res = Response::text(self.to_string()).status(self.status);
+        res.error = Some(self);
+        res
+    }
+}
+
+// swaps responses that came from an Error for whatever the hook renders
+pub(crate) fn render_with(handler: Handler, on_error: Arc<ErrorHandler>) -> Handler {
+    Box::new(move |req: &Request| {
+        let res = handler(req);
+        let on_error = Arc::clone(&on_error);
+        Box::pin(async move {
+            let res = res.await;
+            match &res.error {
+                Some(err) => {
+                    let mut rendered = on_error(err, req);
+                    // keep headers the error response had on top, like Allow on a 405
+                    for (key, value) in res.headers {
+                        if !key.eq_ignore_ascii_case("content-type")
+                            && !rendered
+                                .headers
+                                .keys()
+                                .any(|k| k.eq_ignore_ascii_case(&key))
+                        {
+                            rendered.headers.insert(key, value);
+                        }
+                    }
+                    rendered.error = res.error;
+                    rendered
+                }
+                None => res,
+            }
+        })
+    })
+}
+
+// a handler that panics (while it's called or in its future) gives a 500 instead of
+// taking the connection down with it. the panic message still goes to stderr
+pub(crate) fn catch_panics(handler: Handler) -> Handler {
+    Box::new(move |req: &Request| {
+        let internal = || Error::from(StatusCode::InternalServerError).into_response();
+        let res = match catch_unwind(AssertUnwindSafe(|| handler(req))) {
+            Ok(res) => res,
+            Err(_) => return Box::pin(std::future::ready(internal())),
+        };
+        Box::pin(async move {
+            AssertUnwindSafe(res)
+                .catch_unwind()
+                .await
+                .unwrap_or_else(|_| internal())
+        })
+    })
+}
+
+impl From<StatusCode> for Error {
+    fn from(status: StatusCode) -> Self {
+        Self::new(status, "")
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        use std::io::ErrorKind;
+        let status = match err.kind() {
+            ErrorKind::NotFound => StatusCode::NotFound,
+            ErrorKind::PermissionDenied => StatusCode::Forbidden,
+            ErrorKind::InvalidInput | ErrorKind::InvalidData => StatusCode::BadRequest,
+            ErrorKind::TimedOut => StatusCode::GatewayTimeout,
+            ErrorKind::FileTooLarge => StatusCode::PayloadTooLarge,
+            _ => StatusCode::InternalServerError,
+        };
+        // the os error text is about the server's files, not something to show the client
+        Self {
+            source: Some(Arc::new(err)),
+            ..Self::from(status)
+        }
+    }
+}
+
+// a link to a route that doesn't exist is a bug on our side
+impl From<UrlError> for Error {
+    fn from(err: UrlError) -> Self {
+        Self::internal(err.to_string())
+    }
+}
+
+// anything that failed to parse came from the client
+macro_rules! bad_request_from {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl From<$t> for Error {
+                fn from(err: $t) -> Self {
+                    Self::bad_request(err.to_string())
+                }
+            }
+        )*
+    };
+}
+
+bad_request_from!(
+    ParseIntError,
+    ParseFloatError,
+    ParseBoolError,
+    Utf8Error,
+    FromUtf8Error,
+    std::net::AddrParseError,
+    std::char::ParseCharError,
+);
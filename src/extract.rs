@@ -0,0 +1,399 @@
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+
+use crate::{
+    error::Error,
+    meta::{BoxFuture, Headers, Method, StatusCode},
+    request::{Context, Request, parse_query_params},
+    response::{IntoResponse, Response},
+};
+
+// what an extractor gives back when it can't make sense of the request.
+// 400 for a malformed request, 422 for a body that parsed but didn't fit
+#[derive(Debug, Clone)]
+pub struct Rejection {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl Rejection {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BadRequest, message)
+    }
+
+    pub fn unprocessable(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UnprocessableEntity, message)
+    }
+}
+
+impl From<Rejection> for Error {
+    fn from(value: Rejection) -> Self {
+        Error::new(value.status, value.message)
+    }
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        Error::from(self).into_response()
+    }
+}
+
+pub trait FromRequest: Sized {
+    fn from_request(req: &Request) -> Result<Self, Rejection>;
+}
+
+//
+// parsing single values and whole maps of them
+//
+
+// one value out of the path, query string or a form, None if it wasn't there
+pub trait FromParam: Sized {
+    fn from_param(value: Option<&str>) -> Result<Self, String>;
+}
+
+macro_rules! from_param_via_from_str {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl FromParam for $t {
+                fn from_param(value: Option<&str>) -> Result<Self, String> {
+                    let value = value.ok_or("missing")?;
+                    value.parse::<$t>().map_err(|_| {
+                        format!("expected {}, got {:?}", stringify!($t), value)
+                    })
+                }
+            }
+
+            impl FromParams for $t {
+                fn from_params(params: &HashMap<String, String>) -> Result<Self, String> {
+                    single_param(params)
+                }
+            }
+        )*
+    };
+}
+
+from_param_via_from_str!(
+    String, bool, char, f32, f64, i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize,
+);
+
+impl<T: FromParam> FromParam for Option<T> {
+    fn from_param(value: Option<&str>) -> Result<Self, String> {
+        value.map(|v| T::from_param(Some(v))).transpose()
+    }
+}
+
+// a whole map of params. scalars take the only value there is, which is what you want for
+// a route with one param. for structs, see from_params!
+pub trait FromParams: Sized {
+    fn from_params(params: &HashMap<String, String>) -> Result<Self, String>;
+}
+
+impl FromParams for HashMap<String, String> {
+    fn from_params(params: &HashMap<String, String>) -> Result<Self, String> {
+        Ok(params.clone())
+    }
+}
+
+fn single_param<T: FromParam>(params: &HashMap<String, String>) -> Result<T, String> {
+    let mut values = params.values();
+    match (values.next(), values.next()) {
+        (value, None) => T::from_param(value.map(String::as_str)),
+        _ => Err(format!(
+            "expected a single value, got {} of them",
+            params.len()
+        )),
+    }
+}
+
+// implements FromParams for a struct by looking each field up by name:
+// struct Filter { page: u32, q: Option<String> }
+// cf::from_params!(Filter { page, q });
+#[macro_export]
+macro_rules! from_params {
+    ($name:ident { $($field:ident),* $(,)? }) => {
+        impl $crate::extract::FromParams for $name {
+            fn from_params(
+                params: &::std::collections::HashMap<String, String>,
+            ) -> Result<Self, String> {
+                Ok(Self {
+                    $($field: $crate::extract::FromParam::from_param(
+                        params.get(stringify!($field)).map(String::as_str),
+                    )
+                    .map_err(|err| format!("{}: {}", stringify!($field), err))?,)*
+                })
+            }
+        }
+    };
+}
+
+//
+// extractors
+//
+
+pub struct Path<T>(pub T);
+
+impl<T: FromParams> FromRequest for Path<T> {
+    fn from_request(req: &Request) -> Result<Self, Rejection> {
+        T::from_params(&req.path_params)
+            .map(Path)
+            .map_err(|err| Rejection::bad_request(format!("invalid path params: {err}")))
+    }
+}
+
+pub struct Query<T>(pub T);
+
+impl<T: FromParams> FromRequest for Query<T> {
+    fn from_request(req: &Request) -> Result<Self, Rejection> {
+        T::from_params(&req.query_params)
+            .map(Query)
+            .map_err(|err| Rejection::bad_request(format!("invalid query: {err}")))
+    }
+}
+
+// an application/x-www-form-urlencoded body
+pub struct Form<T>(pub T);
+
+impl<T: FromParams> FromRequest for Form<T> {
+    fn from_request(req: &Request) -> Result<Self, Rejection> {
+        let body = std::str::from_utf8(&req.body)
+            .map_err(|_| Rejection::bad_request("form body is not valid utf-8"))?;
+        T::from_params(&parse_query_params(body))
+            .map(Form)
+            .map_err(|err| Rejection::unprocessable(format!("invalid form: {err}")))
+    }
+}
+
+// the raw body
+pub struct Bytes(pub Vec<u8>);
+
+impl FromRequest for Bytes {
+    fn from_request(req: &Request) -> Result<Self, Rejection> {
+        Ok(Bytes(req.body.to_vec()))
+    }
+}
+
+// the body as text
+impl FromRequest for String {
+    fn from_request(req: &Request) -> Result<Self, Rejection> {
+        req.text()
+            .ok_or_else(|| Rejection::bad_request("body is not valid utf-8"))
+    }
+}
+
+// all of the headers, keys are lowercase
+pub struct HeaderMap(pub Headers);
+
+impl FromRequest for HeaderMap {
+    fn from_request(req: &Request) -> Result<Self, Rejection> {
+        Ok(HeaderMap(req.headers.clone()))
+    }
+}
+
+// a header you know the name of at compile time
+pub trait Header: Sized {
+    const NAME: &'static str;
+    fn decode(value: &str) -> Option<Self>;
+}
+
+pub struct TypedHeader<H>(pub H);
+
+impl<H: Header> FromRequest for TypedHeader<H> {
+    fn from_request(req: &Request) -> Result<Self, Rejection> {
+        let value = req
+            .header(H::NAME)
+            .ok_or_else(|| Rejection::bad_request(format!("missing header {}", H::NAME)))?;
+        H::decode(value)
+            .map(TypedHeader)
+            .ok_or_else(|| Rejection::bad_request(format!("invalid header {}", H::NAME)))
+    }
+}
+
+macro_rules! string_headers {
+    ($($name:ident = $header:literal),* $(,)?) => {
+        $(
+            #[derive(Debug, Clone)]
+            pub struct $name(pub String);
+
+            impl Header for $name {
+                const NAME: &'static str = $header;
+                fn decode(value: &str) -> Option<Self> {
+                    Some(Self(value.to_string()))
+                }
+            }
+        )*
+    };
+}
+
+string_headers! {
+    Authorization = "authorization",
+    ContentType = "content-type",
+    Host = "host",
+    UserAgent = "user-agent",
+}
+
+impl FromRequest for Context {
+    fn from_request(req: &Request) -> Result<Self, Rejection> {
+        Ok(req.context.clone())
+    }
+}
+
+// a typed value some middleware put into the context. if it's not there, that's on the
+// server and not the client, so it's a 500
+pub struct Ctx<T>(pub T);
+
+impl<T: Clone + Send + Sync + 'static> FromRequest for Ctx<T> {
+    fn from_request(req: &Request) -> Result<Self, Rejection> {
+        req.context.value::<T>().cloned().map(Ctx).ok_or_else(|| {
+            Rejection::new(
+                StatusCode::InternalServerError,
+                format!("no {} in the context", std::any::type_name::<T>()),
+            )
+        })
+    }
+}
+
+// shared state registered with Server::state, also a 500 if it's missing
+pub struct State<T>(pub Arc<T>);
+
+impl<T> std::ops::Deref for State<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Send + Sync + 'static> FromRequest for State<T> {
+    fn from_request(req: &Request) -> Result<Self, Rejection> {
+        req.state.arc::<T>().map(State).ok_or_else(|| {
+            Rejection::new(
+                StatusCode::InternalServerError,
+                format!("no {} registered as state", std::any::type_name::<T>()),
+            )
+        })
+    }
+}
+
+impl FromRequest for SocketAddr {
+    fn from_request(req: &Request) -> Result<Self, Rejection> {
+        Ok(req.peer_addr)
+    }
+}
+
+impl FromRequest for Method {
+    fn from_request(req: &Request) -> Result<Self, Rejection> {
+        Ok(req.method.clone())
+    }
+}
+
+impl FromRequest for Request {
+    fn from_request(req: &Request) -> Result<Self, Rejection> {
+        Ok(req.clone())
+    }
+}
+
+// never fails, you just get None
+impl<T: FromRequest> FromRequest for Option<T> {
+    fn from_request(req: &Request) -> Result<Self, Rejection> {
+        Ok(T::from_request(req).ok())
+    }
+}
+
+// for when you want to handle the rejection yourself
+impl<T: FromRequest> FromRequest for Result<T, Rejection> {
+    fn from_request(req: &Request) -> Result<Self, Rejection> {
+        Ok(T::from_request(req))
+    }
+}
+
+//
+// handlers
+//
+
+// a function whose arguments can all be extracted from a request
+pub trait ExtractHandler<Args>: Send + Sync + 'static {
+    fn call(&self, req: &Request) -> Response;
+}
+
+pub trait AsyncExtractHandler<Args>: Send + Sync + 'static {
+    fn call(&self, req: &Request) -> BoxFuture<'static, Response>;
+}
+
+macro_rules! extract_handlers {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> ExtractHandler<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + Send + Sync + 'static,
+            R: IntoResponse,
+            $($arg: FromRequest,)*
+        {
+            #[allow(non_snake_case, unused_variables)]
+            fn call(&self, req: &Request) -> Response {
+                $(
+                    let $arg = match $arg::from_request(req) {
+                        Ok(value) => value,
+                        Err(rejection) => return rejection.into_response(),
+                    };
+                )*
+                self($($arg),*).into_response()
+            }
+        }
+
+        impl<F, Fut, R, $($arg),*> AsyncExtractHandler<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = R> + Send + 'static,
+            R: IntoResponse,
+            $($arg: FromRequest,)*
+        {
+            #[allow(non_snake_case, unused_variables)]
+            fn call(&self, req: &Request) -> BoxFuture<'static, Response> {
+                $(
+                    let $arg = match $arg::from_request(req) {
+                        Ok(value) => value,
+                        Err(rejection) => {
+                            return Box::pin(std::future::ready(rejection.into_response()));
+                        }
+                    };
+                )*
+                let fut = self($($arg),*);
+                Box::pin(async move { fut.await.into_response() })
+            }
+        }
+    };
+}
+
+extract_handlers!();
+extract_handlers!(A);
+extract_handlers!(A, B);
+extract_handlers!(A, B, C);
+extract_handlers!(A, B, C, D);
+extract_handlers!(A, B, C, D, E);
+extract_handlers!(A, B, C, D, E, G);
+extract_handlers!(A, B, C, D, E, G, H);
+extract_handlers!(A, B, C, D, E, G, H, I);
+
+// turns an extractor handler into something Server::get and friends take. they can't take
+// it directly without losing type inference on plain |req| closures
+// .get("/user/{id}", extract::with(|Path(id): Path<u64>| format!("user {id}")))
+pub fn with<H, Args>(handler: H) -> impl Fn(&Request) -> Response + Send + Sync + 'static
+where
+    H: ExtractHandler<Args>,
+{
+    move |req: &Request| handler.call(req)
+}
+
+// same as with, but for Server::get_async and friends
+pub fn with_async<H, Args>(
+    handler: H,
+) -> impl Fn(Request) -> BoxFuture<'static, Response> + Send + Sync + 'static
+where
+    H: AsyncExtractHandler<Args>,
+{
+    move |req: Request| handler.call(&req)
+}
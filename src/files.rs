@@ -0,0 +1,551 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::SystemTime,
+};
+
+use crate::{
+    conditional,
+    error::Error,
+    meta::{self, Handler, StatusCode, guess_content_type, http_date, json_string},
+    request::{Request, percent_decode_path, percent_encode},
+    response::{IntoResponse, Response},
+};
+
+// how a dir gets served, Router::static_files takes one of these. static_route is the
+// same thing with the defaults
+// StaticFiles::new("./share").listing(true)
+#[derive(Clone)]
+pub struct StaticFiles {
+    dir: PathBuf,
+    listing: bool,
+    show_hidden: bool,
+    precompressed: bool,
+    cache: Option<FileCache>,
+}
+
+impl StaticFiles {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            listing: false,
+            show_hidden: false,
+            precompressed: true,
+            cache: None,
+        }
+    }
+
+    // list what's in a dir that has no index.html instead of a 404. html by default, json
+    // with ?format=json or an Accept: application/json, sorted by ?sort=name|size|modified
+    // and ?order=asc|desc
+    #[must_use]
+    pub fn listing(mut self, listing: bool) -> Self {
+        self.listing = listing;
+        self
+    }
+
+    // put dotfiles in listings too
+    #[must_use]
+    pub fn show_hidden(mut self, show_hidden: bool) -> Self {
+        self.show_hidden = show_hidden;
+        self
+    }
+
+    // serve app.js.br or app.js.gz instead of app.js when they're there and the client's
+    // Accept-Encoding takes them. on by default, it's just a stat or two per request
+    #[must_use]
+    pub fn precompressed(mut self, precompressed: bool) -> Self {
+        self.precompressed = precompressed;
+        self
+    }
+
+    // keep small files in memory instead of opening them on every request, see FileCache
+    #[must_use]
+    pub fn cache(mut self, cache: FileCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub(crate) fn into_handler(self) -> Handler {
+        let files = Arc::new(self);
+        meta::handler(move |req| {
+            // join it piece by piece, a leading slash would make join() throw the dir away
+            let mut full_path = files.dir.clone();
+            match file_path(req) {
+                Ok(parts) => full_path.extend(parts),
+                Err(status) => {
+                    return Box::pin(std::future::ready(Error::from(status).into_response()));
+                }
+            }
+
+            let files = files.clone();
+            Box::pin(async move {
+                match files.open(full_path, req).await {
+                    Ok(res) => res,
+                    Err(_) => Error::from(StatusCode::NotFound).into_response(),
+                }
+            })
+        })
+    }
+
+    // a file (or the index.html of a dir, or a listing of it), streamed from disk without
+    // blocking the executor
+    async fn open(&self, path: PathBuf, req: &Request) -> std::io::Result<Response> {
+        if !smol::fs::metadata(&path).await?.is_dir() {
+            return self.file(path, req).await;
+        }
+        let index = path.join("index.html");
+        if !self.listing || smol::fs::metadata(&index).await.is_ok() {
+            return self.file(index, req).await;
+        }
+        // no ../ out of the dir being served
+        let at_root = path == self.dir;
+        let show_hidden = self.show_hidden;
+        let entries = smol::unblock(move || read_entries(&path, show_hidden)).await?;
+        Ok(listing(entries, req, at_root))
+    }
+
+    async fn file(&self, path: PathBuf, req: &Request) -> std::io::Result<Response> {
+        let res = match self.compressed_sibling(&path, req).await {
+            // the type is the one of the original, not application/gzip
+            Some((sibling, encoding)) => {
+                let mut res = self
+                    .load(sibling)
+                    .await?
+                    .header("Content-Type", guess_content_type(&path))
+                    .header("Content-Encoding", encoding);
+                // `gzip -k` keeps the mtime, so the etag gets the encoding too or it could
+                // match the plain one's
+                if let Some(etag) = res.headers.get("ETag") {
+                    let etag = format!("{}-{encoding}\"", etag.trim_end_matches('"'));
+                    res = res.header("ETag", etag);
+                }
+                res
+            }
+            None => self.load(path).await?,
+        };
+        // caches have to keep the compressed and the plain one apart
+        let res = if self.precompressed {
+            res.header("Vary", "Accept-Encoding")
+        } else {
+            res
+        };
+        Ok(res.conditional(req).range(req))
+    }
+
+    async fn load(&self, path: PathBuf) -> std::io::Result<Response> {
+        match &self.cache {
+            Some(cache) => cache.load(path).await,
+            None => Response::file(path).await,
+        }
+    }
+
+    // br is smaller, so it wins when the client takes both
+    async fn compressed_sibling(
+        &self,
+        path: &Path,
+        req: &Request,
+    ) -> Option<(PathBuf, &'static str)> {
+        if !self.precompressed {
+            return None;
+        }
+        let accept = req.header("accept-encoding")?;
+        for (encoding, extension) in [("br", "br"), ("gzip", "gz")] {
+            if !accepts_encoding(accept, encoding) {
+                continue;
+            }
+            let mut sibling = path.as_os_str().to_owned();
+            sibling.push(".");
+            sibling.push(extension);
+            let sibling = PathBuf::from(sibling);
+            if smol::fs::metadata(&sibling)
+                .await
+                .is_ok_and(|metadata| metadata.is_file())
+            {
+                return Some((sibling, encoding));
+            }
+        }
+        None
+    }
+}
+
+// an lru cache of file contents for StaticFiles::cache. every request still stats the
+// file, and an entry whose mtime or size changed gets read again. clone it to keep a
+// handle for the counters
+// let cache = FileCache::new(8 * 1024 * 1024);
+// .static_files("/assets", StaticFiles::new("./dist").cache(cache.clone()))
+#[derive(Clone)]
+pub struct FileCache {
+    max_bytes: u64,
+    max_file_size: u64,
+    inner: Arc<CacheInner>,
+}
+
+#[derive(Default)]
+struct CacheInner {
+    entries: Mutex<CacheEntries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    files: HashMap<PathBuf, CachedFile>,
+    bytes: u64,
+    // bumped on every use, the entry with the lowest one goes first
+    clock: u64,
+}
+
+struct CachedFile {
+    contents: Arc<[u8]>,
+    modified: SystemTime,
+    last_used: u64,
+}
+
+impl FileCache {
+    // holds up to `max_bytes` of file contents
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            max_bytes,
+            max_file_size: max_bytes.min(256 * 1024),
+            inner: Arc::default(),
+        }
+    }
+
+    // files bigger than this (256kb by default) are streamed from disk like without a cache
+    #[must_use]
+    pub fn max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = max_file_size.min(self.max_bytes);
+        self
+    }
+
+    // requests served from memory
+    pub fn hits(&self) -> u64 {
+        self.inner.hits.load(Ordering::Relaxed)
+    }
+
+    // requests that had to read the file, because it wasn't cached or had changed.
+    // files too big to cache don't count
+    pub fn misses(&self) -> u64 {
+        self.inner.misses.load(Ordering::Relaxed)
+    }
+
+    // how much file contents it's holding right now
+    pub fn bytes(&self) -> u64 {
+        self.lock().bytes
+    }
+
+    pub fn clear(&self) {
+        *self.lock() = CacheEntries::default();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheEntries> {
+        // the map is never left half updated, so a panic elsewhere doesn't matter
+        self.inner
+            .entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    async fn load(&self, path: PathBuf) -> std::io::Result<Response> {
+        let metadata = smol::fs::metadata(&path).await?;
+        let (len, modified) = (metadata.len(), metadata.modified()?);
+        if len > self.max_file_size {
+            return Response::file(path).await;
+        }
+
+        let cached = {
+            let mut entries = self.lock();
+            entries.clock += 1;
+            let clock = entries.clock;
+            entries
+                .files
+                .get_mut(&path)
+                .filter(|file| file.modified == modified && file.contents.len() as u64 == len)
+                .map(|file| {
+                    file.last_used = clock;
+                    file.contents.clone()
+                })
+        };
+        let contents = match cached {
+            Some(contents) => {
+                self.inner.hits.fetch_add(1, Ordering::Relaxed);
+                contents
+            }
+            None => {
+                self.inner.misses.fetch_add(1, Ordering::Relaxed);
+                let contents: Arc<[u8]> = smol::fs::read(&path).await?.into();
+                // it changed between the stat and the read, serve it but don't keep it
+                if contents.len() as u64 == len {
+                    self.insert(path.clone(), contents.clone(), modified);
+                }
+                contents
+            }
+        };
+
+        let len = contents.len() as u64;
+        let mut res = Response::shared_bytes(contents, guess_content_type(&path));
+        for (name, value) in conditional::file_validators(len, modified) {
+            res = res.header(name, value);
+        }
+        Ok(res)
+    }
+
+    fn insert(&self, path: PathBuf, contents: Arc<[u8]>, modified: SystemTime) {
+        let mut entries = self.lock();
+        entries.clock += 1;
+        let last_used = entries.clock;
+        let len = contents.len() as u64;
+        if let Some(old) = entries.files.insert(
+            path,
+            CachedFile {
+                contents,
+                modified,
+                last_used,
+            },
+        ) {
+            entries.bytes -= old.contents.len() as u64;
+        }
+        entries.bytes += len;
+
+        while entries.bytes > self.max_bytes {
+            let Some(oldest) = entries
+                .files
+                .iter()
+                .min_by_key(|(_, file)| file.last_used)
+                .map(|(path, _)| path.clone())
+            else {
+                break;
+            };
+            if let Some(file) = entries.files.remove(&oldest) {
+                entries.bytes -= file.contents.len() as u64;
+            }
+        }
+    }
+}
+
+// the decoded pieces of the {*filepath} a static route matched, without empty ones and
+// `.`s. decoding first means %2e%2e is caught as well as ..
+pub(crate) fn file_path(req: &Request) -> Result<Vec<String>, StatusCode> {
+    let file_path = req.path_params.get("filepath").map_or("", String::as_str);
+    let mut parts = Vec::new();
+    for part in file_path.split('/') {
+        let part = percent_decode_path(part);
+        match part.as_str() {
+            "" | "." => {}
+            ".." => return Err(StatusCode::ImATeapot),
+            part if part.contains(['/', '\\', '\0']) => return Err(StatusCode::NotFound),
+            _ => parts.push(part),
+        }
+    }
+    Ok(parts)
+}
+
+// "gzip, deflate, br;q=0.5" takes br, "br;q=0" or "*;q=0" doesn't. a named encoding
+// overrides *
+fn accepts_encoding(header: &str, encoding: &str) -> bool {
+    let mut wildcard = None;
+    for item in header.split(',') {
+        let mut params = item.split(';');
+        let name = params.next().unwrap_or("").trim();
+        let q = params
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if name.eq_ignore_ascii_case(encoding) {
+            return q > 0.0;
+        }
+        if name == "*" {
+            wildcard = Some(q > 0.0);
+        }
+    }
+    wildcard.unwrap_or(false)
+}
+
+struct Entry {
+    name: String,
+    dir: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+fn read_entries(path: &Path, show_hidden: bool) -> std::io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !show_hidden && name.starts_with('.') {
+            continue;
+        }
+        // follows symlinks, so a link to a dir shows up as one
+        let Ok(metadata) = std::fs::metadata(entry.path()) else {
+            continue;
+        };
+        entries.push(Entry {
+            name,
+            dir: metadata.is_dir(),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        });
+    }
+    Ok(entries)
+}
+
+fn listing(mut entries: Vec<Entry>, req: &Request, at_root: bool) -> Response {
+    let sort = req.query_param("sort").unwrap_or("name");
+    let descending = req.query_param("order") == Some("desc");
+    entries.sort_by(|a, b| {
+        let order = match sort {
+            "size" => a.size.cmp(&b.size),
+            "modified" => a.modified.cmp(&b.modified),
+            _ => a.name.cmp(&b.name),
+        };
+        let order = if descending { order.reverse() } else { order };
+        // dirs always come first
+        b.dir.cmp(&a.dir).then(order)
+    });
+
+    let wants_json = req.query_param("format") == Some("json")
+        || req
+            .header("accept")
+            .is_some_and(|accept| accept.contains("application/json"));
+    if wants_json {
+        return Response::json(listing_json(&entries));
+    }
+    Response::html(listing_html(
+        &entries, &req.route, at_root, sort, descending,
+    ))
+}
+
+// [{"name": "a.txt", "dir": false, "size": 12, "modified": "Sun, 06 Nov 1994 08:49:37 GMT"}]
+fn listing_json(entries: &[Entry]) -> String {
+    let entries: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            format!(
+                r#"{{"name": {}, "dir": {}, "size": {}, "modified": {}}}"#,
+                json_string(&entry.name),
+                entry.dir,
+                entry.size,
+                entry
+                    .modified
+                    .map_or("null".to_string(), |m| json_string(&http_date(m)))
+            )
+        })
+        .collect();
+    format!("[{}]", entries.join(", "))
+}
+
+fn listing_html(
+    entries: &[Entry],
+    route: &str,
+    at_root: bool,
+    sort: &str,
+    descending: bool,
+) -> String {
+    // links are absolute, so they work with or without a trailing slash on the dir
+    let base = route.trim_end_matches('/');
+    let title = html_escape(&percent_decode_path(&format!("{base}/")));
+    let mut html = format!(
+        "<!doctype html>\n<html><head><meta charset=\"utf-8\"><title>{title}</title></head>\n<body>\n<h1>{title}</h1>\n<table>\n<tr>"
+    );
+    for (column, label) in [("name", "name"), ("size", "size"), ("modified", "modified")] {
+        // clicking the column it's sorted by flips the order
+        let order = if sort == column && !descending {
+            "desc"
+        } else {
+            "asc"
+        };
+        html += &format!("<th><a href=\"?sort={column}&amp;order={order}\">{label}</a></th>");
+    }
+    html += "</tr>\n";
+    if !at_root {
+        html += &format!(
+            "<tr><td><a href=\"{}/\">../</a></td><td></td><td></td></tr>\n",
+            html_escape(parent(base))
+        );
+    }
+    for entry in entries {
+        let slash = if entry.dir { "/" } else { "" };
+        let size = if entry.dir {
+            "-".to_string()
+        } else {
+            entry.size.to_string()
+        };
+        let modified = entry.modified.map(http_date).unwrap_or_default();
+        html += &format!(
+            "<tr><td><a href=\"{}/{}{slash}\">{}{slash}</a></td><td>{size}</td><td>{modified}</td></tr>\n",
+            html_escape(base),
+            percent_encode(&entry.name),
+            html_escape(&entry.name),
+        );
+    }
+    html += "</table>\n</body></html>\n";
+    html
+}
+
+// "/share/a/b" -> "/share/a"
+fn parent(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+fn html_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(filepath: &str) -> Result<Vec<String>, StatusCode> {
+        let mut req = Request::blank(([127, 0, 0, 1], 0).into(), Default::default());
+        req.path_params
+            .insert("filepath".to_string(), filepath.to_string());
+        file_path(&req)
+    }
+
+    #[test]
+    fn splits_and_decodes() {
+        assert_eq!(path("a/b.txt"), Ok(vec!["a".into(), "b.txt".into()]));
+        assert_eq!(path("a//./b"), Ok(vec!["a".into(), "b".into()]));
+        assert_eq!(path("my%20file+1.txt"), Ok(vec!["my file+1.txt".into()]));
+        assert_eq!(path(""), Ok(vec![]));
+        assert_eq!(path("..a/b..c"), Ok(vec!["..a".into(), "b..c".into()]));
+    }
+
+    #[test]
+    fn rejects_traversal() {
+        for filepath in ["..", "a/../b", "a/..", "%2e%2e", "a/%2E%2E/b", ".%2e/etc"] {
+            assert_eq!(path(filepath), Err(StatusCode::ImATeapot), "{filepath:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_smuggled_separators() {
+        for filepath in [
+            "a%2fb",
+            "a%2F..",
+            "..%5cetc",
+            "a\\b",
+            "a%00.txt",
+            "%2e%2e%2fetc",
+        ] {
+            assert_eq!(path(filepath), Err(StatusCode::NotFound), "{filepath:?}");
+        }
+    }
+}
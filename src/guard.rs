@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use crate::request::Request;
+
+// a check a request has to pass for a route to match it, on top of the method and path.
+// any Fn(&Request) -> bool works, these are just the common ones
+pub type Guard = Arc<dyn Fn(&Request) -> bool + Send + Sync>;
+
+// the header has `value`, or has it in a comma separated list, like
+// header("accept", "application/json") for "Accept: text/html, application/json"
+pub fn header(name: &str, value: &str) -> impl Fn(&Request) -> bool + Send + Sync + 'static {
+    let name = name.to_string();
+    let value = value.to_string();
+    move |req| {
+        req.header(&name).is_some_and(|header| {
+            header
+                .split(',')
+                .any(|item| media_type(item).eq_ignore_ascii_case(&value))
+        })
+    }
+}
+
+// the host name the request was for, without the port
+pub fn host(host: &str) -> impl Fn(&Request) -> bool + Send + Sync + 'static {
+    let host = host.to_string();
+    move |req| {
+        req.host()
+            .is_some_and(|name| name.eq_ignore_ascii_case(&host))
+    }
+}
+
+// the body's Content-Type, ignoring parameters like charset
+pub fn content_type(content_type: &str) -> impl Fn(&Request) -> bool + Send + Sync + 'static {
+    let content_type = content_type.to_string();
+    move |req| {
+        req.header("content-type")
+            .is_some_and(|header| media_type(header).eq_ignore_ascii_case(&content_type))
+    }
+}
+
+// "application/json; charset=utf-8" -> "application/json"
+fn media_type(value: &str) -> &str {
+    value.split(';').next().unwrap_or("").trim()
+}
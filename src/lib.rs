@@ -1,5 +1,15 @@
+pub mod conditional;
+pub mod constraint;
+pub mod embed;
+pub mod error;
+pub mod extract;
+pub mod files;
+pub mod guard;
 pub mod meta;
+pub mod middleware;
+pub mod range;
 pub mod request;
 pub mod response;
+pub mod router;
 pub mod server;
-pub mod middleware;
+pub(crate) mod tree;
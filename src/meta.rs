@@ -3,12 +3,26 @@ use std::{
     fmt::Display,
     io::{Write, stdout},
     path::Path,
+    pin::Pin,
     str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use crate::{request::Request, response::Response};
 
-pub type Handler = Box<dyn Fn(&Request) -> Response + Send + Sync>;
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+// every route ends up as one of these, sync handlers just return a ready future
+pub type Handler = Box<dyn for<'a> Fn(&'a Request) -> BoxFuture<'a, Response> + Send + Sync>;
+
+// boxes a closure into a Handler, which is needed for the compiler to tie the future's
+// lifetime to the request: handler(move |req| Box::pin(async move { ... }))
+pub fn handler<F>(f: F) -> Handler
+where
+    F: for<'a> Fn(&'a Request) -> BoxFuture<'a, Response> + Send + Sync + 'static,
+{
+    Box::new(f)
+}
 
 pub type Headers = HashMap<String, String>;
 
@@ -157,6 +171,66 @@ pub fn guess_content_type(path: &Path) -> &'static str {
     }
 }
 
+const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// "Sun, 06 Nov 1994 08:49:37 GMT", the only date format http wants us to send
+pub fn http_date(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rem) = (secs / 86400, secs % 86400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{}, {day:02} {} {year} {:02}:{:02}:{:02} GMT",
+        DAYS[(days % 7) as usize],
+        MONTHS[month as usize - 1],
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+// reads back what http_date writes. the two obsolete formats are rare enough to not bother
+pub fn parse_http_date(s: &str) -> Option<SystemTime> {
+    let mut parts = s.split_whitespace().skip(1);
+    let day: u64 = parts.next()?.parse().ok()?;
+    let month = parts.next()?;
+    let month = MONTHS.iter().position(|m| *m == month)? as u64 + 1;
+    let year: i64 = parts.next()?.parse().ok()?;
+    let mut time = parts.next()?.split(':').map(|n| n.parse::<u64>().ok());
+    let (h, m, sec) = (time.next()??, time.next()??, time.next()??);
+    if parts.next() != Some("GMT") || !(1..=31).contains(&day) || h > 23 || m > 59 || sec > 60 {
+        return None;
+    }
+    let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(days * 86400 + h * 3600 + m * 60 + sec))
+}
+
+// howard hinnant's date algorithms, days since 1970-01-01 and back
+fn days_from_civil(year: i64, month: u64, day: u64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy as i64;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u64, u64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u64;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u64;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
 pub fn print_banner(host: &str) {
     fn make_line(input: &str) -> String {
         "\x1b[97m\x1b[0m".to_owned() + input + "\x1b[90m\x1b[1m"
@@ -182,3 +256,113 @@ pub fn print_banner(host: &str) {
     _ = stdout().write_all(&banner.into_bytes());
     _ = stdout().flush();
 }
+
+// a json string literal, quotes included
+pub(crate) fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// the route table under the banner, see Server::print_routes
+pub fn print_routes(routes: &[crate::router::RouteInfo]) {
+    let path =
+        |r: &crate::router::RouteInfo| format!("{}{}", r.host.as_deref().unwrap_or(""), r.pattern);
+    let width = routes.iter().map(|r| path(r).len()).max().unwrap_or(0);
+    let mut table = String::new();
+    for route in routes {
+        let name = route.name.as_deref().unwrap_or("");
+        table += &format!(
+            "  \x1b[33m\x1b[1m{:<7}\x1b[97m\x1b[0m {:<width$}  \x1b[90m{:<12} {} mw\x1b[0m\n",
+            route.method.to_string(),
+            path(route),
+            name,
+            route.middleware,
+        );
+    }
+    table.push('\n');
+    _ = stdout().write_all(&table.into_bytes());
+    _ = stdout().flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn formats_http_dates() {
+        // the example from the rfc
+        assert_eq!(http_date(at(784111777)), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(http_date(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(http_date(at(951782400)), "Tue, 29 Feb 2000 00:00:00 GMT");
+        assert_eq!(http_date(at(1709251199)), "Thu, 29 Feb 2024 23:59:59 GMT");
+        // before the epoch there's nothing to send
+        assert_eq!(
+            http_date(UNIX_EPOCH - Duration::from_secs(1)),
+            http_date(UNIX_EPOCH)
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let times = [
+            0, 784111777, 951782400, 978307199, 1709251199, 1735689600, 4102444800,
+        ];
+        for secs in times {
+            assert_eq!(
+                parse_http_date(&http_date(at(secs))),
+                Some(at(secs)),
+                "{secs}"
+            );
+        }
+        // every day for a few years, leap days included
+        for day in 0..4 * 366 {
+            let secs = 946684800 + day * 86400 + 12345;
+            assert_eq!(
+                parse_http_date(&http_date(at(secs))),
+                Some(at(secs)),
+                "{secs}"
+            );
+        }
+    }
+
+    #[test]
+    fn drops_subsecond_precision() {
+        let time = at(784111777) + Duration::from_millis(999);
+        assert_eq!(parse_http_date(&http_date(time)), Some(at(784111777)));
+    }
+
+    #[test]
+    fn rejects_broken_dates() {
+        for s in [
+            "",
+            "Sun, 06 Nov 1994 08:49:37",
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+            "Sun, 06 Foo 1994 08:49:37 GMT",
+            "Sun, 00 Nov 1994 08:49:37 GMT",
+            "Sun, 32 Nov 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Sun, 06 Nov 1994 08:60:00 GMT",
+            "Sun, 06 Nov 1994 08:49 GMT",
+            "Sun, 06 Nov 1969 08:49:37 GMT",
+            // the obsolete formats
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+        ] {
+            assert_eq!(parse_http_date(s), None, "{s:?}");
+        }
+    }
+}
@@ -1,32 +1,76 @@
-use std::time::SystemTime;
+use std::{sync::Arc, time::SystemTime};
 
-use crate::{meta::Handler, request::Request};
+use crate::{meta::Handler, request::Request, response::Response};
 
 pub type Middleware = Box<dyn Fn(Handler) -> Handler + Send + Sync>;
 
+// wraps a handler in a stack of middleware, the first one ends up outermost
+pub(crate) fn wrap(handler: Arc<Handler>, middleware: &[Middleware]) -> Handler {
+    let mut h: Handler = Box::new(move |req: &Request| handler(req));
+    for mw in middleware.iter().rev() {
+        h = mw(h);
+    }
+    h
+}
+
+// the rest of the chain, handed to middleware made with from_fn
+#[derive(Clone)]
+pub struct Next(Arc<Handler>);
+
+impl Next {
+    pub async fn run(&self, req: &Request) -> Response {
+        (self.0)(req).await
+    }
+}
+
+// lets you write middleware as an async fn instead of wrapping handlers by hand:
+// .mw(from_fn(|req, next| async move { next.run(&req).await }))
+pub fn from_fn<F, Fut>(f: F) -> impl Fn(Handler) -> Handler + Send + Sync + 'static
+where
+    F: Fn(Request, Next) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Response> + Send + 'static,
+{
+    let f = Arc::new(f);
+    move |handler: Handler| {
+        let f = Arc::clone(&f);
+        let next = Next(Arc::new(handler));
+        Box::new(move |req: &Request| {
+            // the future can't borrow the request, so it gets its own copy. the body is
+            // shared, so that's just the headers and params
+            Box::pin(f(req.clone(), next.clone()))
+        })
+    }
+}
+
 pub fn logger(handler: Handler) -> Handler {
     Box::new(move |req: &Request| {
         let start = SystemTime::now();
         let res = handler(req);
-        let elapsed = match start.elapsed() {
-            Ok(r) => r,
-            Err(err) => {
-                eprintln!("(logger mw) system time error: {err}");
-                return res;
+        Box::pin(async move {
+            let res = res.await;
+            let elapsed = match start.elapsed() {
+                Ok(r) => r,
+                Err(err) => {
+                    eprintln!("(logger mw) system time error: {err}");
+                    return res;
+                }
+            };
+            println!("{} request to {} took {:?}", req.method, req.route, elapsed);
+            if res.status as u16 > 299 {
+                println!("^^^ {}:\n{:?}", res.status.as_str(), res.body);
             }
-        };
-        println!("{} request to {} took {:?}", req.method, req.route, elapsed);
-        if res.status as u16 > 299 {
-            println!("^^^ {}:\n{:?}", res.status.as_str(), res.body);
-        }
-        res
+            res
+        })
     })
 }
 
 pub fn cors(handler: Handler) -> Handler {
     Box::new(move |req: &Request| {
-        handler(req)
-            .header("Access-Control-Allow-Origin", "*")
-            .header("Access-Control-Expose-Headers", "Content-Type")
+        let res = handler(req);
+        Box::pin(async move {
+            res.await
+                .header("Access-Control-Allow-Origin", "*")
+                .header("Access-Control-Expose-Headers", "Content-Type")
+        })
     })
 }
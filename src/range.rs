@@ -0,0 +1,252 @@
+// Range requests, so video players can seek and downloads can resume. works on any
+// response with a Text, Bytes, Static, Shared or File body, see Response::range
+
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    meta::StatusCode,
+    request::Request,
+    response::{Body, Response},
+};
+
+// more ranges than this in one header and the client just gets the whole thing
+const MAX_RANGES: usize = 16;
+
+// the inclusive byte ranges a Range header asks for out of `len` bytes. None if the
+// header is broken (it gets ignored then), and an empty list if none of it fits
+pub fn parse_range(header: &str, len: u64) -> Option<Vec<(u64, u64)>> {
+    let (unit, specs) = header.split_once('=')?;
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+    let mut ranges = Vec::new();
+    for spec in specs.split(',') {
+        let (start, end) = spec.trim().split_once('-')?;
+        let range = match (start.trim(), end.trim()) {
+            ("", "") => return None,
+            // the last n bytes
+            ("", n) => {
+                let n: u64 = n.parse().ok()?;
+                (n > 0 && len > 0).then(|| (len - n.min(len), len - 1))
+            }
+            (start, "") => {
+                let start: u64 = start.parse().ok()?;
+                (start < len).then(|| (start, len - 1))
+            }
+            (start, end) => {
+                let (start, end): (u64, u64) = (start.parse().ok()?, end.parse().ok()?);
+                if end < start {
+                    return None;
+                }
+                (start < len).then(|| (start, end.min(len - 1)))
+            }
+        };
+        ranges.extend(range);
+    }
+    (ranges.len() <= MAX_RANGES).then_some(ranges)
+}
+
+// turns a full 200 response into a 206 (or a 416) if the request asked for a range
+pub(crate) fn apply(mut res: Response, req: &Request) -> Response {
+    let len = match &res.body {
+        Some(Body::Text(s)) => s.len() as u64,
+        Some(Body::Bytes(b)) => b.len() as u64,
+        Some(Body::Static(b)) => b.len() as u64,
+        Some(Body::Shared { len, .. }) => *len as u64,
+        Some(Body::File { len, .. }) => *len,
+        _ => return res,
+    };
+    if res.status != StatusCode::Ok {
+        return res;
+    }
+    res = res.header("Accept-Ranges", "bytes");
+
+    let Some(header) = req.header("range") else {
+        return res;
+    };
+    if let Some(validator) = req.header("if-range")
+        && !if_range_matches(&res, validator)
+    {
+        return res;
+    }
+    let Some(ranges) = parse_range(header, len) else {
+        return res;
+    };
+
+    if ranges.is_empty() {
+        return Response::empty()
+            .status(StatusCode::RangeNotSatisfiable)
+            .header("Content-Range", format!("bytes */{len}"));
+    }
+    let Some(body) = res.body.as_ref() else {
+        return res;
+    };
+
+    let (body, content_range, content_type) = if let [(start, end)] = ranges[..] {
+        let Some(part) = slice(body, start, end) else {
+            return res;
+        };
+        (part, Some(format!("bytes {start}-{end}/{len}")), None)
+    } else {
+        // several ranges go in one multipart/byteranges body, each with its own headers
+        let content_type = res
+            .headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("content-type"))
+            .map_or("application/octet-stream", |(_, v)| v.as_str());
+        let boundary = boundary();
+        let mut parts = Vec::new();
+        for (start, end) in ranges {
+            let Some(part) = slice(body, start, end) else {
+                return res;
+            };
+            let head = format!(
+                "--{boundary}\r\nContent-Type: {content_type}\r\nContent-Range: bytes {start}-{end}/{len}\r\n\r\n"
+            );
+            parts.push(Body::Bytes(head.into_bytes()));
+            parts.push(part);
+            parts.push(Body::Bytes(b"\r\n".to_vec()));
+        }
+        parts.push(Body::Bytes(format!("--{boundary}--\r\n").into_bytes()));
+        let content_type = format!("multipart/byteranges; boundary={boundary}");
+        (Body::Parts(parts), None, Some(content_type))
+    };
+
+    let replaced = |k: &str| {
+        k.eq_ignore_ascii_case("content-length")
+            || (content_type.is_some() && k.eq_ignore_ascii_case("content-type"))
+    };
+    res.headers.retain(|k, _| !replaced(k));
+    res.body = Some(body);
+    res = res.status(StatusCode::PartialContent);
+    if let Some(content_range) = content_range {
+        res = res.header("Content-Range", content_range);
+    }
+    if let Some(content_type) = content_type {
+        res = res.header("Content-Type", content_type);
+    }
+    res
+}
+
+// If-Range has the ETag or Last-Modified the client's copy had, the range only makes
+// sense if it's still the same. weak etags never match
+fn if_range_matches(res: &Response, validator: &str) -> bool {
+    let header = |name: &str| {
+        res.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    };
+    let validator = validator.trim();
+    if validator.starts_with('"') {
+        header("etag").is_some_and(|etag| etag == validator)
+    } else {
+        header("last-modified").is_some_and(|modified| modified == validator)
+    }
+}
+
+// bytes start..=end of the body, None for ones that can't be sliced. file parts all
+// share the one open file, every read says where it starts
+fn slice(body: &Body, start: u64, end: u64) -> Option<Body> {
+    let (from, to) = (start as usize, end as usize + 1);
+    Some(match body {
+        Body::Text(s) => Body::Bytes(s.as_bytes()[from..to].to_vec()),
+        Body::Bytes(b) => Body::Bytes(b[from..to].to_vec()),
+        Body::Static(b) => Body::Static(&b[from..to]),
+        Body::Shared {
+            bytes,
+            start: offset,
+            ..
+        } => Body::Shared {
+            bytes: Arc::clone(bytes),
+            start: offset + from,
+            len: to - from,
+        },
+        Body::File {
+            file,
+            start: offset,
+            ..
+        } => Body::File {
+            file: Arc::clone(file),
+            start: offset + start,
+            len: end - start + 1,
+        },
+        _ => return None,
+    })
+}
+
+// doesn't need to be random, just not in the body
+fn boundary() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    format!("cf-{nanos:x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_ranges() {
+        assert_eq!(parse_range("bytes=0-9", 100), Some(vec![(0, 9)]));
+        assert_eq!(parse_range("bytes=90-", 100), Some(vec![(90, 99)]));
+        assert_eq!(
+            parse_range("Bytes= 0-0 , 5-6", 100),
+            Some(vec![(0, 0), (5, 6)])
+        );
+    }
+
+    #[test]
+    fn suffix_ranges() {
+        assert_eq!(parse_range("bytes=-10", 100), Some(vec![(90, 99)]));
+        // more than there is means all of it
+        assert_eq!(parse_range("bytes=-500", 100), Some(vec![(0, 99)]));
+        // the last 0 bytes can't be satisfied
+        assert_eq!(parse_range("bytes=-0", 100), Some(vec![]));
+    }
+
+    #[test]
+    fn backwards_ranges_are_broken() {
+        assert_eq!(parse_range("bytes=10-5", 100), None);
+        assert_eq!(parse_range("bytes=0-1,10-5", 100), None);
+    }
+
+    #[test]
+    fn past_the_end() {
+        // the end gets cut down to the last byte
+        assert_eq!(parse_range("bytes=50-500", 100), Some(vec![(50, 99)]));
+        // a start past the end doesn't fit at all
+        assert_eq!(parse_range("bytes=100-200", 100), Some(vec![]));
+        assert_eq!(parse_range("bytes=100-", 100), Some(vec![]));
+        assert_eq!(parse_range("bytes=0-1,200-300", 100), Some(vec![(0, 1)]));
+    }
+
+    #[test]
+    fn empty_body() {
+        assert_eq!(parse_range("bytes=0-0", 0), Some(vec![]));
+        assert_eq!(parse_range("bytes=0-", 0), Some(vec![]));
+        assert_eq!(parse_range("bytes=-5", 0), Some(vec![]));
+    }
+
+    #[test]
+    fn broken_headers() {
+        assert_eq!(parse_range("items=0-1", 100), None);
+        assert_eq!(parse_range("bytes=-", 100), None);
+        assert_eq!(parse_range("bytes=a-b", 100), None);
+        assert_eq!(parse_range("bytes=5", 100), None);
+        assert_eq!(parse_range("0-5", 100), None);
+    }
+
+    #[test]
+    fn too_many_ranges() {
+        let specs = |n: usize| (0..n).map(|i| format!("{i}-{i}")).collect::<Vec<_>>();
+        let header = format!("bytes={}", specs(MAX_RANGES).join(","));
+        assert_eq!(parse_range(&header, 100).map(|r| r.len()), Some(MAX_RANGES));
+        let header = format!("bytes={}", specs(MAX_RANGES + 1).join(","));
+        assert_eq!(parse_range(&header, 100), None);
+    }
+}
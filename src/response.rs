@@ -1,15 +1,57 @@
-use std::{collections::HashMap, fmt::Write, pin::Pin};
+use std::{collections::HashMap, fmt::Write, path::Path, pin::Pin, sync::Arc};
 
 use async_net::TcpStream;
 use smol::io::AsyncWriteExt;
 
-use crate::meta::{Headers, StatusCode};
+use crate::{
+    conditional,
+    error::Error,
+    meta::{Headers, StatusCode, guess_content_type},
+    range,
+    request::Request,
+};
 
 pub enum Body {
     Text(String),
     Bytes(Vec<u8>),
+    // baked into the binary, like embedded assets, so it never gets copied
+    Static(&'static [u8]),
+    // `len` bytes from `start` of something shared, like a cached file, so sending it
+    // (or a range of it) doesn't copy it
+    Shared {
+        bytes: Arc<[u8]>,
+        start: usize,
+        len: usize,
+    },
     Stream(Pin<Box<dyn Fn(TcpStream) -> ResultFuture + Send + Sync>>),
+    // `len` bytes of the file from `start`, sent a chunk at a time. shared so the parts of
+    // a range response can all read from it
+    File {
+        file: Arc<std::fs::File>,
+        start: u64,
+        len: u64,
+    },
+    // sent one after the other, like the parts of a multipart/byteranges response
+    Parts(Vec<Body>),
 }
+
+impl Body {
+    // None for streams, they don't know their length up front
+    fn len(&self) -> Option<u64> {
+        match self {
+            Body::Text(s) => Some(s.len() as u64),
+            Body::Bytes(b) => Some(b.len() as u64),
+            Body::Static(b) => Some(b.len() as u64),
+            Body::Shared { len, .. } => Some(*len as u64),
+            Body::File { len, .. } => Some(*len),
+            Body::Parts(parts) => parts.iter().map(Body::len).sum(),
+            Body::Stream(_) => None,
+        }
+    }
+}
+
+// how much of a file gets read into memory at once
+const FILE_CHUNK: usize = 64 * 1024;
 pub type ResultFuture = Pin<Box<dyn Future<Output = std::io::Result<()>> + Send>>;
 pub type VoidFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
 
@@ -18,7 +60,19 @@ impl std::fmt::Debug for Body {
         match self {
             Self::Text(arg0) => f.debug_tuple("Text").field(arg0).finish(),
             Self::Bytes(arg0) => f.debug_tuple("Bytes").field(arg0).finish(),
+            Self::Static(arg0) => f.debug_tuple("Static").field(&arg0.len()).finish(),
+            Body::Shared { start, len, .. } => f
+                .debug_struct("Shared")
+                .field("start", start)
+                .field("len", len)
+                .finish(),
             Body::Stream(_) => todo!(),
+            Body::File { start, len, .. } => f
+                .debug_struct("File")
+                .field("start", start)
+                .field("len", len)
+                .finish(),
+            Body::Parts(parts) => f.debug_tuple("Parts").field(parts).finish(),
         }
     }
 }
@@ -28,6 +82,8 @@ pub struct Response {
     pub status: StatusCode,
     pub headers: Headers,
     pub body: Option<Body>,
+    // set when the response came from an Error, so Server::on_error can render it again
+    pub error: Option<Error>,
 }
 
 impl Response {
@@ -48,6 +104,44 @@ impl Response {
         Self::new_with_body(Body::Bytes(bytes)).header("Content-Type", content_type)
     }
 
+    // like bytes(), for an include_bytes! or anything else that lives forever
+    pub fn static_bytes(bytes: &'static [u8], content_type: &str) -> Self {
+        Self::new_with_body(Body::Static(bytes)).header("Content-Type", content_type)
+    }
+
+    // like bytes(), for something that's shared with whatever else holds on to it
+    pub fn shared_bytes(bytes: Arc<[u8]>, content_type: &str) -> Self {
+        let len = bytes.len();
+        Self::new_with_body(Body::Shared {
+            bytes,
+            start: 0,
+            len,
+        })
+        .header("Content-Type", content_type)
+    }
+
+    // streams a file from disk instead of reading all of it into memory first
+    pub async fn file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let content_type = guess_content_type(&path);
+        // opening can block, so it happens on smol's thread pool
+        let file = smol::unblock(move || std::fs::File::open(path)).await?;
+        let metadata = file.metadata()?;
+        let len = metadata.len();
+        let mut res = Self::new_with_body(Body::File {
+            file: Arc::new(file),
+            start: 0,
+            len,
+        })
+        .header("Content-Type", content_type);
+        if let Ok(modified) = metadata.modified() {
+            for (name, value) in conditional::file_validators(len, modified) {
+                res = res.header(name, value);
+            }
+        }
+        Ok(res)
+    }
+
     pub fn stream<F>(stream: F) -> Self
     where
         F: Fn(TcpStream) -> ResultFuture + Send + Sync + 'static,
@@ -74,6 +168,7 @@ impl Response {
             status: StatusCode::NoContent,
             headers: HashMap::new(),
             body: None,
+            error: None,
         }
     }
 
@@ -102,22 +197,38 @@ impl Response {
             status: StatusCode::Ok,
             headers: HashMap::from([("Connection".into(), "keep-alive".into())]),
             body: Some(body),
+            error: None,
         }
     }
 
     pub fn finalize(mut self) -> Self {
-        if let Some(Body::Text(s)) = &self.body {
-            self.headers
-                .entry("Content-Length".into())
-                .or_insert(s.len().to_string());
-        } else if let Some(Body::Bytes(b)) = &self.body {
+        if let Some(len) = self.body.as_ref().and_then(Body::len) {
             self.headers
                 .entry("Content-Length".into())
-                .or_insert(b.len().to_string());
+                .or_insert(len.to_string());
         }
         self
     }
 
+    // modifiers that need the request
+
+    // answers a Range request with just the bytes that were asked for (a 206, or a 416
+    // if none of them exist) and tells clients ranges work. for text, bytes and file
+    // bodies of a 200, anything else is left alone
+    // Response::bytes(video, "video/mp4").range(req)
+    #[must_use]
+    pub fn range(self, req: &Request) -> Self {
+        range::apply(self, req)
+    }
+
+    // swaps the response for an empty 304 if the client's copy is still good, going by
+    // If-None-Match against the ETag or If-Modified-Since against Last-Modified. file()
+    // sets both of those, for anything else set them yourself
+    #[must_use]
+    pub fn conditional(self, req: &Request) -> Self {
+        conditional::apply(self, req)
+    }
+
     pub async fn write_to(&self, mut stream: TcpStream) -> std::io::Result<()> {
         let mut header_str = format!("HTTP/1.1 {}\r\n", self.status.as_str());
         for (k, v) in &self.headers {
@@ -130,10 +241,34 @@ impl Response {
         match &self.body {
             Some(Body::Text(s)) => stream.write_all(s.as_bytes()).await?,
             Some(Body::Bytes(b)) => stream.write_all(b).await?,
+            Some(Body::Static(b)) => stream.write_all(b).await?,
+            Some(Body::Shared { bytes, start, len }) => {
+                stream.write_all(&bytes[*start..start + len]).await?
+            }
             Some(Body::Stream(body_stream)) => {
                 let stream = stream.clone();
                 smol::spawn(body_stream(stream)).detach();
             }
+            Some(Body::File { file, start, len }) => {
+                write_file(&mut stream, file, *start, *len).await?;
+            }
+            Some(Body::Parts(parts)) => {
+                for part in parts {
+                    match part {
+                        Body::Text(s) => stream.write_all(s.as_bytes()).await?,
+                        Body::Bytes(b) => stream.write_all(b).await?,
+                        Body::Static(b) => stream.write_all(b).await?,
+                        Body::Shared { bytes, start, len } => {
+                            stream.write_all(&bytes[*start..start + len]).await?
+                        }
+                        Body::File { file, start, len } => {
+                            write_file(&mut stream, file, *start, *len).await?;
+                        }
+                        // nothing makes these
+                        Body::Stream(_) | Body::Parts(_) => {}
+                    }
+                }
+            }
             None => {}
         }
 
@@ -141,6 +276,49 @@ impl Response {
     }
 }
 
+// reads go through smol's thread pool, so a big file never blocks the executor. each
+// one reads a single chunk at an offset, so only that chunk is ever in memory
+async fn write_file(
+    stream: &mut TcpStream,
+    file: &Arc<std::fs::File>,
+    start: u64,
+    len: u64,
+) -> std::io::Result<()> {
+    let mut buf = Vec::new();
+    let mut pos = start;
+    let end = start + len;
+    while pos < end {
+        let want = FILE_CHUNK.min((end - pos) as usize);
+        let file = Arc::clone(file);
+        let n;
+        (buf, n) = smol::unblock(move || {
+            buf.resize(want, 0);
+            let n = read_at(&file, &mut buf, pos)?;
+            Ok::<_, std::io::Error>((buf, n))
+        })
+        .await?;
+        if n == 0 {
+            // it got shorter since we sent the Content-Length
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        stream.write_all(&buf[..n]).await?;
+        pos += n as u64;
+    }
+    Ok(())
+}
+
+// reads from an offset without going through the file's cursor, which is shared
+#[cfg(unix)]
+fn read_at(file: &std::fs::File, buf: &mut [u8], pos: u64) -> std::io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, pos)
+}
+
+// this one moves the cursor, but nothing else reads from it
+#[cfg(windows)]
+fn read_at(file: &std::fs::File, buf: &mut [u8], pos: u64) -> std::io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, pos)
+}
+
 impl Default for Response {
     fn default() -> Self {
         Response::text("")
@@ -159,11 +337,31 @@ impl From<&str> for Response {
     }
 }
 
+// what a handler can return. anything that's Into<Response> already is one, and so is a
+// Result of two of them, which is what lets handlers use ?
+pub trait IntoResponse {
+    fn into_response(self) -> Response;
+}
+
+impl<T: Into<Response>> IntoResponse for T {
+    fn into_response(self) -> Response {
+        self.into()
+    }
+}
+
+impl<T: IntoResponse, E: IntoResponse> IntoResponse for Result<T, E> {
+    fn into_response(self) -> Response {
+        match self {
+            Ok(value) => value.into_response(),
+            Err(err) => err.into_response(),
+        }
+    }
+}
+
 pub struct SseSink {
     stream: smol::net::TcpStream,
 }
 
-
 impl SseSink {
     pub async fn send(&mut self, data: &str) -> std::io::Result<()> {
         // it needs a double newline at the end
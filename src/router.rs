@@ -0,0 +1,614 @@
+use std::{collections::HashMap, fmt::Display, sync::Arc};
+
+use crate::{
+    embed::Embedded,
+    files::StaticFiles,
+    guard::Guard,
+    meta::{self, Handler, Method, json_string},
+    middleware::{self, Middleware},
+    request::{Request, percent_encode},
+    response::IntoResponse,
+    tree::{RouteSegment, parse_pattern},
+};
+
+// a bunch of routes with their own middleware, which only wraps those routes.
+// Server::group hands you one of these, or build one yourself and Server::mount it
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<RouteDef>,
+    middleware: Vec<Middleware>,
+    // this router's own (with an empty prefix) and the ones of routers mounted on it
+    fallbacks: Vec<Fallback>,
+    // index of the route layer() applies to, cleared by anything that adds several
+    last: Option<usize>,
+}
+
+// a route as it was registered, before the server turns it into something it can match
+pub(crate) struct RouteDef {
+    pub method: Method,
+    pub path: String,
+    // for url_for, from get_named() and friends
+    pub name: Option<String>,
+    pub handler: Arc<Handler>,
+    // from layer(), only wrapped around the handler once the route is built
+    pub layers: Vec<Middleware>,
+    // how much middleware from groups and mounted routers is already in `handler`
+    pub wrapped: usize,
+    // from guard(), all of them have to pass for the route to match
+    pub guards: Vec<Guard>,
+}
+
+// a registered route, as Server::routes shows it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteInfo {
+    pub method: Method,
+    // None for the server's own routes, see Server::host
+    pub host: Option<String>,
+    pub pattern: String,
+    pub name: Option<String>,
+    // everything wrapped around it: mw(), groups, mounted routers and layer()
+    pub middleware: usize,
+}
+
+// [{"method": "GET", "host": null, "pattern": "/user/{name}", "name": "user", "middleware": 2}, ...]
+pub(crate) fn routes_json(routes: &[RouteInfo]) -> String {
+    let entries: Vec<String> = routes
+        .iter()
+        .map(|route| {
+            format!(
+                r#"{{"method": "{}", "host": {}, "pattern": {}, "name": {}, "middleware": {}}}"#,
+                route.method,
+                route
+                    .host
+                    .as_deref()
+                    .map_or("null".to_string(), json_string),
+                json_string(&route.pattern),
+                route
+                    .name
+                    .as_deref()
+                    .map_or("null".to_string(), json_string),
+                route.middleware
+            )
+        })
+        .collect();
+    format!("[{}]", entries.join(", "))
+}
+
+// runs for requests under `prefix` that no route matched
+pub(crate) struct Fallback {
+    pub prefix: String,
+    pub handler: Arc<Handler>,
+}
+
+impl Fallback {
+    pub fn covers(&self, path: &str) -> bool {
+        let prefix = self.prefix.trim_end_matches('/');
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+}
+
+impl RouteDef {
+    // the handler with its layers on, outermost first
+    pub fn build_handler(&self) -> Arc<Handler> {
+        if self.layers.is_empty() {
+            Arc::clone(&self.handler)
+        } else {
+            Arc::new(middleware::wrap(Arc::clone(&self.handler), &self.layers))
+        }
+    }
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn add_route(&mut self, method: Method, path: &str, name: Option<&str>, handler: Handler) {
+        self.last = Some(self.routes.len());
+        self.routes.push(RouteDef {
+            method,
+            path: path.to_string(),
+            name: name.map(str::to_string),
+            handler: Arc::new(handler),
+            layers: Vec::new(),
+            wrapped: 0,
+            guards: Vec::new(),
+        });
+    }
+
+    // layer() and guard() only go on a route added right before them, anything else in
+    // between ends that
+    pub(crate) fn end_route(&mut self) {
+        self.last = None;
+    }
+
+    pub(crate) fn routes(&self) -> &[RouteDef] {
+        &self.routes
+    }
+
+    pub(crate) fn fallbacks(&self) -> &[Fallback] {
+        &self.fallbacks
+    }
+
+    // takes in another router's routes and fallbacks under a prefix, wrapped in its
+    // middleware so they keep it
+    fn merge(&mut self, prefix: &str, other: Router) {
+        let middleware = other.middleware;
+        let wrap = |handler: Arc<Handler>| {
+            if middleware.is_empty() {
+                handler
+            } else {
+                Arc::new(middleware::wrap(handler, &middleware))
+            }
+        };
+        for route in other.routes {
+            self.routes.push(RouteDef {
+                handler: wrap(route.build_handler()),
+                wrapped: route.wrapped + route.layers.len() + middleware.len(),
+                method: route.method,
+                path: join_path(prefix, &route.path),
+                name: route.name,
+                layers: Vec::new(),
+                guards: route.guards,
+            });
+        }
+        for fallback in other.fallbacks {
+            self.fallbacks.push(Fallback {
+                prefix: join_path(prefix, &fallback.prefix),
+                handler: wrap(fallback.handler),
+            });
+        }
+        self.end_route();
+    }
+
+    // chainable methods
+
+    // only wraps routes in this router, no matter if they were added before or after it
+    #[must_use]
+    pub fn mw<F>(mut self, ware: F) -> Self
+    where
+        F: Fn(Handler) -> Handler + Send + Sync + 'static,
+    {
+        self.middleware.push(Box::new(ware));
+        self.end_route();
+        self
+    }
+
+    // middleware for just the route added right before this. it's wrapped around the
+    // handler once when the route is built, and the first layer added is the outermost
+    // .get("/admin", admin).layer(admin_only).layer(rate_limit)
+    #[must_use]
+    pub fn layer<F>(mut self, ware: F) -> Self
+    where
+        F: Fn(Handler) -> Handler + Send + Sync + 'static,
+    {
+        self.last
+            .and_then(|i| self.routes.get_mut(i))
+            .expect("layer() has to come right after adding a route")
+            .layers
+            .push(Box::new(ware));
+        self
+    }
+
+    // only match the route added right before this if the request passes `check` too,
+    // otherwise routing carries on like the route wasn't there. stacks, all have to pass
+    // .get("/x", json_x).guard(guard::header("accept", "application/json")).get("/x", x)
+    #[must_use]
+    pub fn guard<F>(mut self, check: F) -> Self
+    where
+        F: Fn(&Request) -> bool + Send + Sync + 'static,
+    {
+        self.last
+            .and_then(|i| self.routes.get_mut(i))
+            .expect("guard() has to come right after adding a route")
+            .guards
+            .push(Arc::new(check));
+        self
+    }
+
+    #[must_use]
+    pub fn route<F, R>(mut self, method: Method, route: &str, handler: F) -> Self
+    where
+        F: Fn(&Request) -> R + Send + Sync + 'static,
+        R: IntoResponse + Send,
+    {
+        self.add_route(method, route, None, sync_handler(handler));
+        self
+    }
+
+    // same as route() but with a name, so links to it can be built with url_for
+    #[must_use]
+    pub fn route_named<F, R>(mut self, method: Method, name: &str, route: &str, handler: F) -> Self
+    where
+        F: Fn(&Request) -> R + Send + Sync + 'static,
+        R: IntoResponse + Send,
+    {
+        self.add_route(method, route, Some(name), sync_handler(handler));
+        self
+    }
+
+    #[must_use]
+    pub fn get_named<F, R>(self, name: &str, route: &str, handler: F) -> Self
+    where
+        F: Fn(&Request) -> R + Send + Sync + 'static,
+        R: IntoResponse + Send,
+    {
+        self.route_named(Method::GET, name, route, handler)
+    }
+
+    #[must_use]
+    pub fn post_named<F, R>(self, name: &str, route: &str, handler: F) -> Self
+    where
+        F: Fn(&Request) -> R + Send + Sync + 'static,
+        R: IntoResponse + Send,
+    {
+        self.route_named(Method::POST, name, route, handler)
+    }
+
+    #[must_use]
+    pub fn get<F, R>(self, route: &str, handler: F) -> Self
+    where
+        F: Fn(&Request) -> R + Send + Sync + 'static,
+        R: IntoResponse + Send,
+    {
+        self.route(Method::GET, route, handler)
+    }
+
+    #[must_use]
+    pub fn post<F, R>(self, route: &str, handler: F) -> Self
+    where
+        F: Fn(&Request) -> R + Send + Sync + 'static,
+        R: IntoResponse + Send,
+    {
+        self.route(Method::POST, route, handler)
+    }
+
+    #[must_use]
+    pub fn route_async<F, Fut, R>(mut self, method: Method, route: &str, handler: F) -> Self
+    where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = R> + Send + 'static,
+        R: IntoResponse,
+    {
+        self.add_route(method, route, None, async_handler(handler));
+        self
+    }
+
+    #[must_use]
+    pub fn get_async<F, Fut, R>(self, route: &str, handler: F) -> Self
+    where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = R> + Send + 'static,
+        R: IntoResponse,
+    {
+        self.route_async(Method::GET, route, handler)
+    }
+
+    #[must_use]
+    pub fn post_async<F, Fut, R>(self, route: &str, handler: F) -> Self
+    where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = R> + Send + 'static,
+        R: IntoResponse,
+    {
+        self.route_async(Method::POST, route, handler)
+    }
+
+    #[must_use]
+    pub fn static_route(self, route: &str, path: &str) -> Self {
+        self.static_files(route, StaticFiles::new(path))
+    }
+
+    // the files of an include_assets!, served like static_route would serve the dir
+    #[must_use]
+    pub fn embedded_route(mut self, route: &str, assets: Embedded) -> Self {
+        self.add_route(
+            Method::GET,
+            &format!("{route}/{{*filepath}}"),
+            None,
+            assets.into_handler(),
+        );
+        self
+    }
+
+    // static_route with options, like directory listings
+    // .static_files("/share", StaticFiles::new("./share").listing(true))
+    #[must_use]
+    pub fn static_files(mut self, route: &str, files: StaticFiles) -> Self {
+        self.add_route(
+            Method::GET,
+            &format!("{route}/{{*filepath}}"),
+            None,
+            files.into_handler(),
+        );
+        self
+    }
+
+    // routes under a prefix with their own middleware, on top of this router's.
+    // .group("/api", |g| g.mw(auth).get("/me", me))
+    #[must_use]
+    pub fn group<F>(mut self, prefix: &str, f: F) -> Self
+    where
+        F: FnOnce(Router) -> Router,
+    {
+        self.merge(prefix, f(Router::new()));
+        self
+    }
+
+    // adds a router that was built on its own, like in another crate, under a prefix.
+    // it keeps its middleware and fallback
+    // .mount("/billing", billing::router())
+    #[must_use]
+    pub fn mount(mut self, prefix: &str, router: Router) -> Self {
+        self.merge(prefix, router);
+        self
+    }
+
+    // what runs for requests under this router that none of its routes match, instead
+    // of the server's fallback (or the 404)
+    #[must_use]
+    pub fn fallback<F, R>(mut self, handler: F) -> Self
+    where
+        F: Fn(&Request) -> R + Send + Sync + 'static,
+        R: IntoResponse + Send,
+    {
+        self.fallbacks.retain(|f| !f.prefix.is_empty());
+        self.fallbacks.push(Fallback {
+            prefix: String::new(),
+            handler: Arc::new(sync_handler(handler)),
+        });
+        self.end_route();
+        self
+    }
+}
+
+// what happens when a path only matches a route with(out) a trailing slash, like /hi/
+// when there's only a /hi route
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrailingSlash {
+    // /hi and /hi/ are different routes
+    #[default]
+    Strict,
+    // send the client to the one that exists with a 301
+    RedirectMoved,
+    // the same with a 308, which keeps the method and body
+    RedirectPermanent,
+    // just serve the one that exists
+    MatchBoth,
+}
+
+// squashes duplicate slashes and resolves . and .. so "//a/./b/../c" routes like "/a/c".
+// a trailing slash stays, the policy above decides what it means
+pub(crate) fn normalize_path(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            // can't go above the root
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    let mut normalized = String::with_capacity(path.len());
+    for part in &parts {
+        normalized.push('/');
+        normalized.push_str(part);
+    }
+    let trailing = path.ends_with('/') || path.ends_with("/.") || path.ends_with("/..");
+    if trailing || parts.is_empty() {
+        normalized.push('/');
+    }
+    normalized
+}
+
+// "/hi" -> "/hi/" and back, None for the root
+pub(crate) fn toggle_trailing_slash(path: &str) -> Option<String> {
+    match path.strip_suffix('/') {
+        Some("") => None,
+        Some(trimmed) => Some(trimmed.to_string()),
+        None => Some(format!("{path}/")),
+    }
+}
+
+// route names and their patterns, for building links instead of formatting paths by
+// hand. you get it from req.url_for or Server::url_for
+#[derive(Debug, Clone, Default)]
+pub struct Urls(Arc<HashMap<String, String>>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UrlError {
+    UnknownRoute(String),
+    MissingParam(String),
+    // a param the route doesn't have, probably a typo
+    ExtraParam(String),
+}
+
+impl Display for UrlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UrlError::UnknownRoute(name) => write!(f, "no route is named {name:?}"),
+            UrlError::MissingParam(param) => write!(f, "missing param {param:?}"),
+            UrlError::ExtraParam(param) => write!(f, "the route has no param {param:?}"),
+        }
+    }
+}
+
+impl std::error::Error for UrlError {}
+
+impl Urls {
+    // panics if two different routes have the same name
+    pub(crate) fn from_routes(routes: &[RouteDef]) -> Self {
+        let mut urls = HashMap::new();
+        for route in routes {
+            let Some(name) = &route.name else { continue };
+            let pattern = format!("/{}", route.path.trim_start_matches('/'));
+            if let Some(existing) = urls.insert(name.clone(), pattern.clone())
+                && existing != pattern
+            {
+                panic!("routes {existing:?} and {pattern:?} are both named {name:?}");
+            }
+        }
+        Self(Arc::new(urls))
+    }
+
+    // url_for("user", &[("name", "bob")]) = "/user/bob". params get percent-encoded, a
+    // {*wildcard} keeps its slashes
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+        let pattern = self
+            .0
+            .get(name)
+            .ok_or_else(|| UrlError::UnknownRoute(name.to_string()))?;
+        let segments = parse_pattern(pattern);
+
+        if let Some((extra, _)) = params.iter().find(|(key, _)| {
+            !segments.iter().any(|segment| match segment {
+                RouteSegment::Param(name, _) | RouteSegment::Wildcard(name) => name == key,
+                RouteSegment::Static(_) => false,
+            })
+        }) {
+            return Err(UrlError::ExtraParam(extra.to_string()));
+        }
+        let value = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| *value)
+                .ok_or_else(|| UrlError::MissingParam(name.to_string()))
+        };
+
+        let mut url = String::with_capacity(pattern.len());
+        for segment in &segments {
+            match segment {
+                RouteSegment::Static(s) => url.push_str(s),
+                RouteSegment::Param(name, _) => url.push_str(&percent_encode(value(name)?)),
+                RouteSegment::Wildcard(name) => {
+                    let parts: Vec<String> = value(name)?.split('/').map(percent_encode).collect();
+                    url.push_str(&parts.join("/"));
+                }
+            }
+        }
+        Ok(url)
+    }
+}
+
+// "/api" + "/users" = "/api/users", and "/api" + "/" = "/api"
+pub(crate) fn join_path(prefix: &str, path: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+    let path = path.trim_start_matches('/');
+    if path.is_empty() && !prefix.is_empty() {
+        prefix.to_string()
+    } else {
+        format!("{prefix}/{path}")
+    }
+}
+
+//
+// turning the different kinds of handlers into a Handler
+//
+
+pub(crate) fn sync_handler<F, R>(handler: F) -> Handler
+where
+    F: Fn(&Request) -> R + Send + Sync + 'static,
+    R: IntoResponse + Send,
+{
+    // Wrap the handler so it always returns a Response
+    meta::handler(move |req| Box::pin(std::future::ready(handler(req).into_response())))
+}
+
+pub(crate) fn async_handler<F, Fut, R>(handler: F) -> Handler
+where
+    F: Fn(Request) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = R> + Send + 'static,
+    R: IntoResponse,
+{
+    meta::handler(move |req| {
+        let fut = handler(req.clone());
+        Box::pin(async move { fut.await.into_response() })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noop(handler: Handler) -> Handler {
+        handler
+    }
+
+    #[test]
+    fn layer_and_guard_go_on_the_last_route() {
+        let router = Router::new()
+            .get("/a", |_req| "a")
+            .layer(noop)
+            .guard(|_req| true);
+        assert_eq!(router.routes()[0].layers.len(), 1);
+        assert_eq!(router.routes()[0].guards.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "has to come right after adding a route")]
+    fn layer_after_fallback() {
+        let _ = Router::new()
+            .get("/a", |_req| "a")
+            .fallback(|_req| "f")
+            .layer(noop);
+    }
+
+    #[test]
+    #[should_panic(expected = "has to come right after adding a route")]
+    fn guard_after_mw() {
+        let _ = Router::new()
+            .get("/a", |_req| "a")
+            .mw(noop)
+            .guard(|_req| true);
+    }
+
+    #[test]
+    #[should_panic(expected = "has to come right after adding a route")]
+    fn layer_after_mount() {
+        let _ = Router::new()
+            .get("/a", |_req| "a")
+            .mount("/b", Router::new())
+            .layer(noop);
+    }
+
+    #[test]
+    fn normalizes_paths() {
+        for (path, normalized) in [
+            ("/", "/"),
+            ("", "/"),
+            ("/a/b", "/a/b"),
+            ("//a///b", "/a/b"),
+            ("/a/./b", "/a/b"),
+            ("/a/b/../c", "/a/c"),
+            ("/a/b/", "/a/b/"),
+            // trailing dot segments still leave a trailing slash
+            ("/a/b/.", "/a/b/"),
+            ("/a/b/..", "/a/"),
+            // nothing goes above the root
+            ("/..", "/"),
+            ("/../../a", "/a"),
+            ("/a/../..", "/"),
+            ("/a/../../b/", "/b/"),
+            (".", "/"),
+            ("..", "/"),
+            // only whole segments are dots
+            ("/a/..b/.c", "/a/..b/.c"),
+            ("/a/.../b", "/a/.../b"),
+        ] {
+            assert_eq!(normalize_path(path), normalized, "{path:?}");
+        }
+    }
+
+    #[test]
+    fn toggles_trailing_slashes() {
+        assert_eq!(toggle_trailing_slash("/hi"), Some("/hi/".to_string()));
+        assert_eq!(toggle_trailing_slash("/hi/"), Some("/hi".to_string()));
+        assert_eq!(toggle_trailing_slash("/a/b/"), Some("/a/b".to_string()));
+        // the root has nothing to toggle to
+        assert_eq!(toggle_trailing_slash("/"), None);
+    }
+}
@@ -1,29 +1,91 @@
 use async_net::{TcpListener, TcpStream};
-use smol::{fs::File, io::AsyncReadExt};
-use std::{collections::HashMap, net::SocketAddr, path::PathBuf, sync::Arc};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, OnceLock},
+};
 
 use crate::{
-    meta::{Handler, Method, StatusCode, guess_content_type, print_banner},
+    constraint::ParamValue,
+    embed::Embedded,
+    error::{self, Error, ErrorHandler},
+    files::StaticFiles,
+    guard::Guard,
+    meta::{Handler, Method, StatusCode, print_banner, print_routes},
     middleware::Middleware,
-    request::Request,
-    response::Response,
+    request::{AppState, Request},
+    response::{IntoResponse, Response},
+    router::{self, Fallback, RouteInfo, Router, TrailingSlash, UrlError, Urls, routes_json},
+    tree::{RouteSegment, Tree, parse_pattern},
 };
 
 pub struct Server {
-    routes: Arc<HashMap<Method, Vec<Route>>>,
+    router: Router,
+    // from host(), in the order they were added
+    hosts: Vec<(String, Router)>,
     middleware: Arc<Vec<Middleware>>,
+    on_error: Option<Arc<ErrorHandler>>,
+    state: AppState,
+    trailing_slash: TrailingSlash,
+    body_limit: Option<usize>,
+    print_routes: bool,
+    // filled in with the route table once the server starts, see debug_routes
+    routes_json: Option<Arc<OnceLock<String>>>,
     addr: SocketAddr,
 }
 
-pub struct Route {
-    pub segments: Vec<RouteSegment>,
-    pub handler: Arc<Handler>,
+// everything a connection needs, put together once when the server starts
+struct App {
+    // the server's own routes, for requests no host() matches
+    site: Site,
+    // exact names first, then wildcards with the longest suffix first
+    hosts: Vec<(String, Site)>,
+    middleware: Arc<Vec<Middleware>>,
+    on_error: Option<Arc<ErrorHandler>>,
+    state: AppState,
+    trailing_slash: TrailingSlash,
+    body_limit: Option<usize>,
+}
+
+impl App {
+    fn site(&self, request: &Request) -> &Site {
+        let Some(host) = request.host() else {
+            return &self.site;
+        };
+        self.hosts
+            .iter()
+            .find(|(pattern, _)| host_matches(pattern, host))
+            .map_or(&self.site, |(_, site)| site)
+    }
+}
+
+// the routes of one host
+struct Site {
+    routes: Routes,
+    // longest prefix first
+    fallbacks: Vec<Fallback>,
+    urls: Urls,
+}
+
+type Routes = HashMap<Method, Tree<Vec<Candidate>>>;
+
+// one of the routes for a pattern. the guarded ones come first, and there's at most
+// one without guards
+struct Candidate {
+    guards: Vec<Guard>,
+    // its own param names, another candidate might've called them something else
+    names: Vec<String>,
+    pattern: String,
+    handler: Arc<Handler>,
 }
 
-pub enum RouteSegment {
-    Static(String),
-    Param(String),
+impl Candidate {
+    fn passes(&self, req: &Request) -> bool {
+        self.guards.iter().all(|guard| guard(req))
+    }
 }
+// the params as strings, and the typed ones as what they parsed to
+type PathParams = (HashMap<String, String>, HashMap<String, ParamValue>);
 
 impl Server {
     pub fn new<A: std::net::ToSocketAddrs>(addr: A) -> Self {
@@ -33,8 +95,15 @@ impl Server {
                 .expect("failed to resolve address!")
                 .next()
                 .expect("no valid addresses?"),
-            routes: Arc::new(HashMap::new()),
+            router: Router::new(),
+            hosts: Vec::new(),
             middleware: Arc::new(Vec::new()),
+            on_error: None,
+            state: AppState::default(),
+            trailing_slash: TrailingSlash::default(),
+            body_limit: None,
+            print_routes: false,
+            routes_json: None,
         }
     }
     fn add_middleware<F>(&mut self, mw: F)
@@ -46,155 +115,222 @@ impl Server {
             .push(Box::new(mw));
     }
 
-    // do not call this after calling serve()
-    fn add_route<F, R>(&mut self, method: Method, path: &str, handler: F)
-    where
-        F: Fn(&Request) -> R + Send + Sync + 'static,
-        R: Into<Response> + Send,
-    {
-        let segments = path
-            .trim_start_matches('/')
-            .split('/')
-            .map(|s| {
-                if s.starts_with('{') && s.ends_with('}') {
-                    RouteSegment::Param(s[1..s.len() - 1].to_string())
-                } else {
-                    RouteSegment::Static(s.to_string())
-                }
-            })
-            .collect();
-
-        // Wrap the handler so it always returns a Response
-        let wrapped = move |req: &Request| handler(req).into();
+    fn build_site(router: &Router) -> Site {
+        Site {
+            routes: Self::build_routes(router),
+            fallbacks: Self::build_fallbacks(router),
+            urls: Urls::from_routes(router.routes()),
+        }
+    }
 
-        let route = Route {
-            segments,
-            handler: Arc::new(Box::new(wrapped)),
-        };
+    // turns what was registered into something we can match requests against
+    fn build_routes(router: &Router) -> Routes {
+        let mut routes = Routes::new();
+        for def in router.routes() {
+            let pattern = normalize_pattern(&def.path);
+            let names = parse_pattern(&pattern)
+                .into_iter()
+                .filter_map(|segment| match segment {
+                    RouteSegment::Param(name, _) | RouteSegment::Wildcard(name) => Some(name),
+                    RouteSegment::Static(_) => None,
+                })
+                .collect();
+            let candidate = Candidate {
+                guards: def.guards.clone(),
+                names,
+                pattern,
+                handler: def.build_handler(),
+            };
+
+            // routes for the same paths can live together as long as they're guarded
+            let tree = routes.entry(def.method.clone()).or_default();
+            let candidates = tree.get_or_insert_with(&candidate.pattern, Vec::new);
+            let unguarded = candidates.iter().position(|c| c.guards.is_empty());
+            match unguarded {
+                Some(i) if candidate.guards.is_empty() => panic!(
+                    "{} {} conflicts with {} {}, they match the same paths",
+                    def.method, candidate.pattern, def.method, candidates[i].pattern
+                ),
+                Some(i) => candidates.insert(i, candidate),
+                None => candidates.push(candidate),
+            }
+        }
+        routes
+    }
 
-        Arc::get_mut(&mut self.routes)
-            .expect("cannot add routes after cloning")
-            .entry(method)
-            .or_default()
-            .push(route);
-    }
-
-    fn add_route_static(&mut self, route: &str, dir_path: &str) {
-        let dir_path = dir_path.to_string();
-
-        self.add_route(
-            Method::GET,
-            &format!("{route}/{{filepath}}"),
-            move |req: &Request| {
-                let dir_path = dir_path.clone();
-
-                let file_path = req.path_params.get("filepath").map_or("", String::as_str);
-
-                if file_path.contains("..") {
-                    return Response::error(StatusCode::ImATeapot); // pathbuf should protect u
-                    // anyways but idk
-                }
-
-                let full_path = PathBuf::from(&dir_path).join(file_path);
-
-                match smol::block_on(async {
-                    if full_path.is_dir() {
-                        let index_path = full_path.join("index.html");
-                        let mut file = File::open(&index_path).await?;
-                        let mut contents = Vec::new();
-                        file.read_to_end(&mut contents).await?;
-                        Ok::<_, std::io::Error>((contents, index_path))
-                    } else {
-                        let mut file = File::open(&full_path).await?;
-                        let mut contents = Vec::new();
-                        file.read_to_end(&mut contents).await?;
-                        Ok((contents, full_path))
-                    }
-                }) {
-                    Ok((contents, path)) => {
-                        let content_type = guess_content_type(&path);
-                        Response::bytes(contents, content_type)
-                    }
-                    Err(_) => Response::error(StatusCode::NotFound),
-                }
-            },
-        );
+    fn build_fallbacks(router: &Router) -> Vec<Fallback> {
+        let mut fallbacks: Vec<Fallback> = router
+            .fallbacks()
+            .iter()
+            .map(|f| Fallback {
+                prefix: f.prefix.clone(),
+                handler: Arc::clone(&f.handler),
+            })
+            .collect();
+        fallbacks.sort_by_key(|f| std::cmp::Reverse(f.prefix.trim_end_matches('/').len()));
+        fallbacks
     }
 
+    // the first route for the path whose guards all pass, if a route's guards fail the
+    // next best one gets tried
     fn match_route<'a>(
-        routes: &'a [Route],
+        routes: &'a Routes,
+        request: &Request,
+        method: &Method,
         path: &str,
-    ) -> Option<(&'a Arc<Handler>, HashMap<String, String>)> {
-        let req_segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
-
-        for route in routes {
-            if route.segments.len() != req_segments.len() {
-                continue;
+    ) -> Option<(&'a Arc<Handler>, PathParams)> {
+        let found = routes.get(method)?.find_where(path, |candidates| {
+            candidates.iter().any(|c| c.passes(request))
+        })?;
+        let candidate = found.value.iter().find(|c| c.passes(request))?;
+        let mut params = HashMap::new();
+        let mut typed = HashMap::new();
+        for (name, param) in candidate.names.iter().zip(found.params) {
+            if let Some(parsed) = param.parsed {
+                typed.insert(name.clone(), parsed);
             }
+            params.insert(name.clone(), param.value.into_owned());
+        }
+        Some((&candidate.handler, (params, typed)))
+    }
 
-            let mut params = HashMap::new();
-            let mut matched = true;
-
-            for (seg, req_seg) in route.segments.iter().zip(req_segments.iter()) {
-                match seg {
-                    RouteSegment::Static(s) if s != (*req_seg) => {
-                        matched = false;
-                        break;
-                    }
-                    RouteSegment::Param(name) => {
-                        params.insert(name.clone(), (*req_seg).to_string());
-                    }
+    // a HEAD request goes to the GET route if there's no HEAD one, the body gets dropped
+    // when the response is sent
+    fn match_method<'a>(
+        routes: &'a Routes,
+        request: &Request,
+        path: &str,
+    ) -> Option<(&'a Arc<Handler>, PathParams)> {
+        Self::match_route(routes, request, &request.method, path).or_else(|| {
+            (request.method == Method::HEAD)
+                .then(|| Self::match_route(routes, request, &Method::GET, path))
+                .flatten()
+        })
+    }
 
-                    RouteSegment::Static(_) => {}
-                }
-            }
+    // the route for the request, or whatever the trailing slash policy does about it
+    // if there's only one on the other side of a slash
+    fn find_handler(
+        site: &Site,
+        trailing_slash: TrailingSlash,
+        request: &Request,
+    ) -> Option<(Arc<Handler>, PathParams)> {
+        let routes = &site.routes;
+        let path = &request.route;
+        if let Some((handler, params)) = Self::match_method(routes, request, path) {
+            return Some((Arc::clone(handler), params));
+        }
+        if trailing_slash == TrailingSlash::Strict {
+            return None;
+        }
+        let other = router::toggle_trailing_slash(path)?;
+        let (handler, params) = Self::match_method(routes, request, &other)?;
+        let status = match trailing_slash {
+            TrailingSlash::RedirectMoved => StatusCode::MovedPermanently,
+            TrailingSlash::RedirectPermanent => StatusCode::PermanentRedirect,
+            _ => return Some((Arc::clone(handler), params)),
+        };
+        let location = if request.query_string.is_empty() {
+            other
+        } else {
+            format!("{other}?{}", request.query_string)
+        };
+        let redirect: Handler = Box::new(move |_req: &Request| {
+            let res = Response::empty()
+                .status(status)
+                .header("Location", location.as_str());
+            Box::pin(std::future::ready(res))
+        });
+        Some((Arc::new(redirect), PathParams::default()))
+    }
 
-            if matched {
-                return Some((&route.handler, params));
+    // nothing matched: a 405 if the path exists for other methods, otherwise the
+    // fallback of the innermost router mounted there, the server's, or a 404
+    fn unmatched(site: &Site, request: &Request) -> Arc<Handler> {
+        let mut allowed: Vec<String> = site
+            .routes
+            .iter()
+            .filter(|(method, _)| {
+                **method != request.method
+                    && Self::match_route(&site.routes, request, method, &request.route).is_some()
+            })
+            .map(|(method, _)| method.to_string())
+            .collect();
+        if !allowed.is_empty() {
+            if allowed.iter().any(|m| m == "GET") && !allowed.iter().any(|m| m == "HEAD") {
+                allowed.push("HEAD".to_string());
             }
+            allowed.sort_unstable();
+            let allow = allowed.join(", ");
+            return Arc::new(Box::new(move |_req: &Request| {
+                let res = Error::from(StatusCode::MethodNotAllowed)
+                    .into_response()
+                    .header("Allow", allow.as_str());
+                Box::pin(std::future::ready(res))
+            }));
+        }
+        match site.fallbacks.iter().find(|f| f.covers(&request.route)) {
+            Some(fallback) => Arc::clone(&fallback.handler),
+            None => Arc::new(Box::new(|_req: &Request| {
+                let res = Error::from(StatusCode::NotFound).into_response();
+                Box::pin(std::future::ready(res))
+            })),
         }
-
-        None
     }
 
-    async fn handle_connection(
-        mut stream: TcpStream,
-        routes: Arc<HashMap<Method, Vec<Route>>>,
-        middleware: Arc<Vec<Middleware>>,
-    ) {
-        let mut request = match Request::from_stream(&mut stream).await {
+    async fn handle_connection(mut stream: TcpStream, app: Arc<App>) {
+        let mut request = match Request::from_stream_limited(&mut stream, app.body_limit).await {
             Ok(req) => req,
             Err(err) => {
                 eprintln!("failed to parse request: {err}");
-                let _ = Response::error(StatusCode::BadRequest)
-                    .write_to(stream)
-                    .await;
+                // a 400, or a 413 if the body was too big
+                let err = Error::from(err);
+                let response = match (&app.on_error, stream.peer_addr()) {
+                    (Some(on_error), Ok(peer_addr)) => {
+                        on_error(&err, &Request::blank(peer_addr, app.state.clone()))
+                    }
+                    _ => err.into_response(),
+                };
+                let _ = response.finalize().write_to(stream).await;
                 return;
             }
         };
 
-        let default_handler: Arc<Handler> = Arc::new(Box::new(|_req: &Request| {
-            Response::error(StatusCode::NotFound)
-        }));
-
-        let (handler, path_params) = routes
-            .get(&request.method)
-            .and_then(|routes| Self::match_route(routes, &request.route))
-            .unwrap_or_else(|| (&default_handler, HashMap::new()));
+        request.route = router::normalize_path(&request.route);
+        let site = app.site(&request);
+        let (handler, (path_params, typed_params)) =
+            Self::find_handler(site, app.trailing_slash, &request)
+                .unwrap_or_else(|| (Self::unmatched(site, &request), Default::default()));
 
         request.path_params = path_params;
-
-        // cant be moved in2 the closure if u dont clone it
-        let handler = Arc::clone(handler);
+        request.typed_params = typed_params;
+        request.state = app.state.clone();
+        request.urls = site.urls.clone();
 
         // start w the base handler and go through the mw backwards
         let mut h: Handler = Box::new(move |req: &Request| handler(req));
-        for mw in middleware.iter().rev() {
+        h = error::catch_panics(h);
+        // errors get rendered before any middleware sees the response
+        if let Some(on_error) = &app.on_error {
+            h = error::render_with(h, Arc::clone(on_error));
+        }
+        for mw in app.middleware.iter().rev() {
             h = mw(h);
         }
 
-        let response = h(&request);
-        if let Err(err) = response.finalize().write_to(stream).await {
+        let response = h(&request).await;
+        if let Some(err) = &response.error
+            && err.status == StatusCode::InternalServerError
+            && let Some(source) = std::error::Error::source(err)
+        {
+            eprintln!("{} {} failed: {source}", request.method, request.route);
+        }
+        let mut response = response.finalize();
+        // same headers as a GET, Content-Length included, just no body
+        if request.method == Method::HEAD {
+            response.body = None;
+        }
+        if let Err(err) = response.write_to(stream).await {
             match err.kind() {
                 std::io::ErrorKind::BrokenPipe | std::io::ErrorKind::ConnectionReset => {}
                 _ => eprintln!("failed to write response: {err}"),
@@ -205,17 +341,36 @@ impl Server {
     async fn serve_async(&self) -> std::io::Result<()> {
         let listener = TcpListener::bind(self.addr).await?;
         print_banner(&self.addr.to_string());
+        let table: Vec<RouteInfo> = self.routes().collect();
+        if self.print_routes {
+            print_routes(&table);
+        }
+        if let Some(json) = &self.routes_json {
+            let _ = json.set(routes_json(&table));
+        }
 
-        let routes = Arc::clone(&self.routes);
-        let middleware = Arc::clone(&self.middleware);
+        let mut hosts: Vec<(String, Site)> = self
+            .hosts
+            .iter()
+            .map(|(pattern, router)| (pattern.clone(), Self::build_site(router)))
+            .collect();
+        hosts.sort_by_key(|(pattern, _)| {
+            (pattern.starts_with("*."), std::cmp::Reverse(pattern.len()))
+        });
+
+        let app = Arc::new(App {
+            site: Self::build_site(&self.router),
+            hosts,
+            middleware: Arc::clone(&self.middleware),
+            on_error: self.on_error.clone(),
+            state: self.state.clone(),
+            trailing_slash: self.trailing_slash,
+            body_limit: self.body_limit,
+        });
 
         loop {
             let (stream, _) = listener.accept().await?;
-
-            let routes = Arc::clone(&routes);
-            let middleware = Arc::clone(&middleware);
-
-            smol::spawn(Self::handle_connection(stream, routes, middleware)).detach();
+            smol::spawn(Self::handle_connection(stream, Arc::clone(&app))).detach();
         }
     }
 
@@ -235,6 +390,7 @@ impl Server {
         F: Fn(Handler) -> Handler + Send + Sync + 'static,
     {
         self.add_middleware(ware);
+        self.router.end_route();
         self
     }
 
@@ -242,38 +398,352 @@ impl Server {
     pub fn route<F, R>(mut self, method: Method, route: &str, handler: F) -> Self
     where
         F: Fn(&Request) -> R + Send + Sync + 'static,
-        R: Into<Response> + Send,
+        R: IntoResponse + Send,
     {
-        self.add_route(method, route, handler);
+        self.router = self.router.route(method, route, handler);
         self
     }
 
     #[must_use]
-    pub fn get<F, R>(mut self, route: &str, handler: F) -> Self
+    pub fn get<F, R>(self, route: &str, handler: F) -> Self
     where
         F: Fn(&Request) -> R + Send + Sync + 'static,
-        R: Into<Response> + Send,
+        R: IntoResponse + Send,
+    {
+        self.route(Method::GET, route, handler)
+    }
+
+    #[must_use]
+    pub fn post<F, R>(self, route: &str, handler: F) -> Self
+    where
+        F: Fn(&Request) -> R + Send + Sync + 'static,
+        R: IntoResponse + Send,
+    {
+        self.route(Method::POST, route, handler)
+    }
+
+    #[must_use]
+    pub fn route_async<F, Fut, R>(mut self, method: Method, route: &str, handler: F) -> Self
+    where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = R> + Send + 'static,
+        R: IntoResponse,
     {
-        self.add_route(Method::GET, route, handler);
+        self.router = self.router.route_async(method, route, handler);
         self
     }
 
     #[must_use]
-    pub fn post<F, R>(mut self, route: &str, handler: F) -> Self
+    pub fn get_async<F, Fut, R>(self, route: &str, handler: F) -> Self
+    where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = R> + Send + 'static,
+        R: IntoResponse,
+    {
+        self.route_async(Method::GET, route, handler)
+    }
+
+    #[must_use]
+    pub fn post_async<F, Fut, R>(self, route: &str, handler: F) -> Self
+    where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = R> + Send + 'static,
+        R: IntoResponse,
+    {
+        self.route_async(Method::POST, route, handler)
+    }
+
+    // a route with a name, so links to it can be built with req.url_for (or
+    // Server::url_for) instead of formatting paths by hand
+    // .get_named("user", "/user/{name}", user)
+    #[must_use]
+    pub fn route_named<F, R>(mut self, method: Method, name: &str, route: &str, handler: F) -> Self
     where
         F: Fn(&Request) -> R + Send + Sync + 'static,
-        R: Into<Response> + Send,
+        R: IntoResponse + Send,
     {
-        self.add_route(Method::POST, route, handler);
+        self.router = self.router.route_named(method, name, route, handler);
+        self
+    }
+
+    #[must_use]
+    pub fn get_named<F, R>(self, name: &str, route: &str, handler: F) -> Self
+    where
+        F: Fn(&Request) -> R + Send + Sync + 'static,
+        R: IntoResponse + Send,
+    {
+        self.route_named(Method::GET, name, route, handler)
+    }
+
+    #[must_use]
+    pub fn post_named<F, R>(self, name: &str, route: &str, handler: F) -> Self
+    where
+        F: Fn(&Request) -> R + Send + Sync + 'static,
+        R: IntoResponse + Send,
+    {
+        self.route_named(Method::POST, name, route, handler)
+    }
+
+    // url_for("user", &[("name", "bob")]) = "/user/bob". errors if there's no route with
+    // that name, or a param is missing or isn't in the route
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+        Urls::from_routes(self.router.routes()).url_for(name, params)
+    }
+
+    // picks the handler for a method and path the way a request would be routed, minus
+    // the socket, and gives back the params the handler would get. only there so
+    // benches/router.rs can time the real thing
+    #[doc(hidden)]
+    pub fn bench_routing(
+        &self,
+    ) -> impl FnMut(Method, &str) -> Option<HashMap<String, String>> + use<> {
+        let site = Self::build_site(&self.router);
+        let trailing_slash = self.trailing_slash;
+        let mut request = Request::blank(self.addr, self.state.clone());
+        move |method, path| {
+            request.method = method;
+            request.route = router::normalize_path(path);
+            let (_, (params, _)) = Self::find_handler(&site, trailing_slash, &request)?;
+            Some(params)
+        }
+    }
+
+    // only match the route added right before this if `check` passes too, otherwise
+    // routing falls through to the next route that fits
+    // .get("/x", json_x).guard(guard::header("accept", "application/json"))
+    #[must_use]
+    pub fn guard<F>(mut self, check: F) -> Self
+    where
+        F: Fn(&Request) -> bool + Send + Sync + 'static,
+    {
+        self.router = self.router.guard(check);
+        self
+    }
+
+    // middleware for just the route added right before this, unlike mw() which wraps
+    // everything. .get("/admin", admin).layer(admin_only)
+    #[must_use]
+    pub fn layer<F>(mut self, ware: F) -> Self
+    where
+        F: Fn(Handler) -> Handler + Send + Sync + 'static,
+    {
+        self.router = self.router.layer(ware);
+        self
+    }
+
+    // shared state for every handler and middleware, get it back with req.state.get::<T>()
+    // or the State<T> extractor. one value per type, so wrap it in a newtype if u need two
+    #[must_use]
+    pub fn state<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.state.insert(value);
+        self.router.end_route();
+        self
+    }
+
+    // customize how errors look, e.g. to wrap them in json. it gets the ones returned from
+    // handlers and rejected extractors, and the ones the server makes itself: 400 for a
+    // request it can't parse, 404, 405, 413 and 500 for a handler that panicked
+    #[must_use]
+    pub fn on_error<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&Error, &Request) -> Response + Send + Sync + 'static,
+    {
+        self.on_error = Some(Arc::new(Box::new(handler)));
+        self.router.end_route();
+        self
+    }
+
+    // what to do about /hi/ when there's only a /hi route (or the other way around).
+    // strict by default, dot segments and double slashes get cleaned up either way
+    #[must_use]
+    pub fn trailing_slash(mut self, policy: TrailingSlash) -> Self {
+        self.trailing_slash = policy;
+        self.router.end_route();
+        self
+    }
+
+    // what to run when no route matches, instead of the 404. still goes through mw()
+    // .fallback(|_req| Response::html(INDEX_HTML))
+    #[must_use]
+    pub fn fallback<F, R>(mut self, handler: F) -> Self
+    where
+        F: Fn(&Request) -> R + Send + Sync + 'static,
+        R: IntoResponse + Send,
+    {
+        self.router = self.router.fallback(handler);
+        self
+    }
+
+    // the biggest body a request can have before it gets a 413, None (the default) for
+    // no limit
+    // .body_limit(Some(2 * 1024 * 1024))
+    #[must_use]
+    pub fn body_limit(mut self, limit: Option<usize>) -> Self {
+        self.body_limit = limit;
+        self.router.end_route();
         self
     }
 
     pub fn static_route(mut self, route: &str, path: &str) -> Self {
-        self.add_route_static(route, path);
+        self.router = self.router.static_route(route, path);
         self
     }
 
+    // the files of an include_assets!, served like static_route would serve the dir
+    // .embedded_route("/static", ASSETS)
+    #[must_use]
+    pub fn embedded_route(mut self, route: &str, assets: Embedded) -> Self {
+        self.router = self.router.embedded_route(route, assets);
+        self
+    }
+
+    // static_route with options, like directory listings
+    // .static_files("/share", StaticFiles::new("./share").listing(true))
+    #[must_use]
+    pub fn static_files(mut self, route: &str, files: StaticFiles) -> Self {
+        self.router = self.router.static_files(route, files);
+        self
+    }
+
+    // routes under a prefix with middleware that only wraps them. groups can be nested
+    // .group("/api", |g| g.mw(auth).get("/me", me).group("/admin", |g| g.mw(admin_only)))
+    #[must_use]
+    pub fn group<F>(mut self, prefix: &str, f: F) -> Self
+    where
+        F: FnOnce(Router) -> Router,
+    {
+        self.router = self.router.group(prefix, f);
+        self
+    }
+
+    // adds a router that was built on its own, like in another crate. its routes go under
+    // the prefix and keep their middleware, and its fallback handles whatever else is
+    // under the prefix. .mount("/billing", billing::router())
+    #[must_use]
+    pub fn mount(mut self, prefix: &str, router: Router) -> Self {
+        self.router = self.router.mount(prefix, router);
+        self
+    }
+
+    // every route that's been registered, in the order it was added. the ones for
+    // host() come after the server's own
+    pub fn routes(&self) -> impl Iterator<Item = RouteInfo> + '_ {
+        let global = self.middleware.len();
+        let hosts = self
+            .hosts
+            .iter()
+            .map(|(pattern, router)| (Some(pattern.as_str()), router));
+        std::iter::once((None, &self.router))
+            .chain(hosts)
+            .flat_map(move |(host, router)| {
+                router.routes().iter().map(move |route| RouteInfo {
+                    method: route.method.clone(),
+                    host: host.map(str::to_string),
+                    pattern: normalize_pattern(&route.path),
+                    name: route.name.clone(),
+                    middleware: global + route.wrapped + route.layers.len(),
+                })
+            })
+    }
+
+    // routes for requests to another host name, by the Host header (or the authority of
+    // an absolute url in the request line). "*.example.test" takes any subdomain, and the
+    // server's own routes get requests no host matches. mw() still wraps all of them
+    // .host("docs.example.test", docs::router())
+    #[must_use]
+    pub fn host(mut self, host: &str, router: Router) -> Self {
+        // mounted like any other router so its own mw() wraps its routes and fallback
+        let router = Router::new().mount("", router);
+        self.hosts.push((host.to_ascii_lowercase(), router));
+        self.router.end_route();
+        self
+    }
+
+    // prints the route table under the banner when the server starts
+    #[must_use]
+    pub fn print_routes(mut self) -> Self {
+        self.print_routes = true;
+        self.router.end_route();
+        self
+    }
+
+    // serves the route table as json at `route`, for debugging. don't leave it on in prod
+    #[must_use]
+    pub fn debug_routes(mut self, route: &str) -> Self {
+        let json = Arc::new(OnceLock::new());
+        self.routes_json = Some(Arc::clone(&json));
+        self.get(route, move |_req| {
+            Response::json(json.get().cloned().unwrap_or_default())
+        })
+    }
+
     pub fn serve(&self) -> std::io::Result<()> {
         smol::block_on(self.serve_async())
     }
 }
+
+// "docs.example.test" only matches itself, "*.example.test" matches any subdomain of
+// example.test but not example.test itself
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix('*') {
+        Some(suffix) => {
+            host.len() > suffix.len()
+                && host
+                    .get(host.len() - suffix.len()..)
+                    .is_some_and(|end| end.eq_ignore_ascii_case(suffix))
+        }
+        None => host.eq_ignore_ascii_case(pattern),
+    }
+}
+
+// "user/{name}" and "/user/{name}" are the same route
+fn normalize_pattern(pattern: &str) -> String {
+    format!("/{}", pattern.trim_start_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn head_goes_to_get_routes() {
+        let mut route = Server::new("127.0.0.1:0")
+            .get("/a/{x}", |_req| "get")
+            .route(Method::HEAD, "/b", |_req| "head")
+            .post("/c", |_req| "post")
+            .bench_routing();
+        assert_eq!(
+            route(Method::HEAD, "/a/1").map(|p| p["x"].clone()),
+            Some("1".into())
+        );
+        assert!(route(Method::HEAD, "/b").is_some());
+        assert!(route(Method::HEAD, "/c").is_none());
+        assert!(route(Method::POST, "/a/1").is_none());
+    }
+
+    #[test]
+    fn exact_hosts() {
+        assert!(host_matches("docs.example.test", "docs.example.test"));
+        assert!(host_matches("docs.example.test", "DOCS.Example.Test"));
+        assert!(!host_matches("docs.example.test", "example.test"));
+        assert!(!host_matches("docs.example.test", "a.docs.example.test"));
+    }
+
+    #[test]
+    fn wildcard_hosts() {
+        assert!(host_matches("*.example.test", "a.example.test"));
+        assert!(host_matches("*.example.test", "a.b.example.test"));
+        assert!(host_matches("*.example.test", "A.EXAMPLE.test"));
+        // the bare domain isn't a subdomain of itself
+        assert!(!host_matches("*.example.test", "example.test"));
+        assert!(!host_matches("*.example.test", ".example.test"));
+        assert!(!host_matches("*.example.test", "badexample.test"));
+        assert!(!host_matches("*.example.test", "example.test.evil"));
+    }
+
+    #[test]
+    fn multibyte_hosts_dont_panic() {
+        assert!(!host_matches("*.example.test", "é.example.tesé"));
+        assert!(host_matches("*.example.test", "é.example.test"));
+    }
+}
@@ -0,0 +1,432 @@
+// a compressed prefix tree for matching paths against route patterns. static text is
+// shared between routes byte by byte, so a lookup only looks at each byte of the path
+// about once, no matter how many routes there are
+
+use std::borrow::Cow;
+
+use crate::{
+    constraint::{Constraint, ParamValue},
+    request::percent_decode_path,
+};
+
+// a piece of a route pattern. statics can span several segments, a param is (the rest of)
+// one segment and a wildcard is everything that's left, slashes included
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteSegment {
+    Static(String),
+    Param(String, Option<Constraint>),
+    Wildcard(String),
+}
+
+// splits "/user/{name}/posts" into [Static("/user/"), Param("name"), Static("/posts")].
+// {id:u64} only matches what fits the constraint, and {*rest} is a wildcard that has to
+// be the last thing in the pattern
+pub fn parse_pattern(pattern: &str) -> Vec<RouteSegment> {
+    let mut segments = Vec::new();
+    let mut rest = pattern;
+    while !rest.is_empty() {
+        match rest.find('{') {
+            Some(0) => {
+                // constraints can have braces of their own, like {n:[0-9]{4}}
+                let mut depth = 0;
+                let end = rest
+                    .char_indices()
+                    .find(|(_, c)| {
+                        match c {
+                            '{' => depth += 1,
+                            '}' => depth -= 1,
+                            _ => {}
+                        }
+                        depth == 0
+                    })
+                    .map(|(i, _)| i)
+                    .unwrap_or_else(|| panic!("unclosed {{ in route {pattern:?}"));
+                let inner = &rest[1..end];
+                rest = &rest[end + 1..];
+                match inner.strip_prefix('*') {
+                    Some(name) if rest.is_empty() => {
+                        segments.push(RouteSegment::Wildcard(name.to_string()));
+                    }
+                    Some(_) => panic!("{{*wildcard}} has to be at the end of route {pattern:?}"),
+                    None => segments.push(match inner.split_once(':') {
+                        Some((name, constraint)) => RouteSegment::Param(
+                            name.to_string(),
+                            Some(Constraint::parse(constraint)),
+                        ),
+                        None => RouteSegment::Param(inner.to_string(), None),
+                    }),
+                }
+            }
+            Some(start) => {
+                segments.push(RouteSegment::Static(rest[..start].to_string()));
+                rest = &rest[start..];
+            }
+            None => {
+                segments.push(RouteSegment::Static(rest.to_string()));
+                rest = "";
+            }
+        }
+    }
+    segments
+}
+
+// static pieces always win over params, and params over wildcards, no matter which route
+// was added first. two routes that would match exactly the same paths share a value
+pub struct Tree<T> {
+    root: Node<T>,
+}
+
+// what a lookup found. params are in the order they appear in the path
+#[derive(Debug)]
+pub struct Match<'t, 'p, T> {
+    pub value: &'t T,
+    pub params: Vec<Param<'p>>,
+}
+
+// params are percent-decoded (only allocating if there was a %), the wildcard is left
+// as it came in
+#[derive(Debug, Clone)]
+pub struct Param<'p> {
+    pub value: Cow<'p, str>,
+    // set if the param had a typed constraint like {id:u64}
+    pub parsed: Option<ParamValue>,
+}
+
+struct Node<T> {
+    // the static bytes this node eats, can end in the middle of a char
+    prefix: Vec<u8>,
+    // no two of these start with the same byte
+    children: Vec<Node<T>>,
+    // every route with a param here with the same constraint shares one of these, the
+    // names live in the leaves. constrained ones come first (see Constraint::priority)
+    // so they get tried first
+    params: Vec<ParamNode<T>>,
+    // takes the rest of the path if nothing else here matched
+    wildcard: Option<T>,
+    value: Option<T>,
+}
+
+struct ParamNode<T> {
+    constraint: Option<Constraint>,
+    node: Node<T>,
+}
+
+impl<T> Default for Tree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Tree<T> {
+    pub fn new() -> Self {
+        Self {
+            root: Node::new(Vec::new()),
+        }
+    }
+
+    // the value at a pattern (or at one that matches the same paths, like /a/{y} for
+    // /a/{x}), after inserting f() if there wasn't one
+    // param names aren't kept, routes that share a value can call them different things
+    pub fn get_or_insert_with(&mut self, pattern: &str, f: impl FnOnce() -> T) -> &mut T {
+        let mut node = &mut self.root;
+        let mut wildcard = false;
+        for segment in parse_pattern(pattern) {
+            node = match segment {
+                RouteSegment::Static(s) => node.insert_static(s.as_bytes()),
+                RouteSegment::Param(_, constraint) => node.insert_param(constraint),
+                RouteSegment::Wildcard(_) => {
+                    wildcard = true;
+                    node
+                }
+            };
+        }
+
+        let slot = if wildcard {
+            &mut node.wildcard
+        } else {
+            &mut node.value
+        };
+        slot.get_or_insert_with(f)
+    }
+
+    // the best match for the path whose value `accept` says yes to. a value it says no to
+    // doesn't end the search, the next best route gets a chance
+    pub fn find_where<'t, 'p, F>(&'t self, path: &'p str, accept: F) -> Option<Match<'t, 'p, T>>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let mut params = Vec::new();
+        let value = self.root.find(path, 0, &mut params, &accept)?;
+        // they were collected on the way back up
+        params.reverse();
+        Some(Match { value, params })
+    }
+}
+
+impl<T> Node<T> {
+    fn new(prefix: Vec<u8>) -> Self {
+        Self {
+            prefix,
+            children: Vec::new(),
+            params: Vec::new(),
+            wildcard: None,
+            value: None,
+        }
+    }
+
+    // walks down (and splits nodes) until `s` is used up, returns the node it ends at
+    fn insert_static(&mut self, s: &[u8]) -> &mut Node<T> {
+        if s.is_empty() {
+            return self;
+        }
+        let Some(i) = self.children.iter().position(|c| c.prefix[0] == s[0]) else {
+            self.children.push(Node::new(s.to_vec()));
+            return self.children.last_mut().unwrap();
+        };
+        let child = &mut self.children[i];
+        let common = child
+            .prefix
+            .iter()
+            .zip(s)
+            .take_while(|(a, b)| a == b)
+            .count();
+        if common < child.prefix.len() {
+            // split the child so the shared part becomes its own node
+            let tail = child.prefix.split_off(common);
+            let mut old = std::mem::replace(child, Node::new(tail));
+            std::mem::swap(&mut old.prefix, &mut child.prefix);
+            child.children.push(old);
+        }
+        child.insert_static(&s[common..])
+    }
+
+    fn insert_param(&mut self, constraint: Option<Constraint>) -> &mut Node<T> {
+        let i = match self.params.iter().position(|p| p.constraint == constraint) {
+            Some(i) => i,
+            None => {
+                // in Constraint::priority order, with the unconstrained one at the end
+                fn rank(c: &Option<Constraint>) -> (bool, Option<(u8, u128, bool, &str)>) {
+                    (c.is_none(), c.as_ref().map(Constraint::priority))
+                }
+                let i = self
+                    .params
+                    .partition_point(|p| rank(&p.constraint) < rank(&constraint));
+                self.params.insert(
+                    i,
+                    ParamNode {
+                        constraint,
+                        node: Node::new(Vec::new()),
+                    },
+                );
+                i
+            }
+        };
+        &mut self.params[i].node
+    }
+
+    // `pos` is how far into `path` this node's prefix got us. param values only get pushed
+    // once the whole path matched, so dead ends don't allocate anything
+    fn find<'t, 'p, F>(
+        &'t self,
+        path: &'p str,
+        pos: usize,
+        params: &mut Vec<Param<'p>>,
+        accept: &F,
+    ) -> Option<&'t T>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let rest = &path.as_bytes()[pos..];
+
+        if rest.is_empty()
+            && let Some(value) = &self.value
+            && accept(value)
+        {
+            return Some(value);
+        }
+
+        if let Some(first) = rest.first()
+            && let Some(child) = self.children.iter().find(|c| c.prefix[0] == *first)
+            && rest.starts_with(&child.prefix)
+            && let Some(found) = child.find(path, pos + child.prefix.len(), params, accept)
+        {
+            return Some(found);
+        }
+
+        if !self.params.is_empty() {
+            // a param is everything up to the next slash, which can be nothing. it always
+            // starts after a whole static piece of the pattern, so on a char boundary
+            let end = rest
+                .iter()
+                .position(|b| *b == b'/')
+                .map_or(path.len(), |i| pos + i);
+            let segment = &path[pos..end];
+            let segment = if segment.contains('%') {
+                Cow::Owned(percent_decode_path(segment))
+            } else {
+                Cow::Borrowed(segment)
+            };
+            for param in &self.params {
+                let parsed = match &param.constraint {
+                    Some(constraint) => match constraint.check(&segment) {
+                        Some(parsed) => parsed,
+                        None => continue,
+                    },
+                    None => None,
+                };
+                if let Some(found) = param.node.find(path, end, params, accept) {
+                    params.push(Param {
+                        value: segment,
+                        parsed,
+                    });
+                    return Some(found);
+                }
+            }
+        }
+
+        let wildcard = self.wildcard.as_ref().filter(|value| accept(value))?;
+        params.push(Param {
+            value: Cow::Borrowed(&path[pos..]),
+            parsed: None,
+        });
+        Some(wildcard)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(patterns: &[&str]) -> Tree<usize> {
+        let mut tree = Tree::new();
+        for (i, pattern) in patterns.iter().enumerate() {
+            tree.get_or_insert_with(pattern, || i);
+        }
+        tree
+    }
+
+    // the index of the pattern that matched, and the param values
+    fn find<'p>(tree: &Tree<usize>, path: &'p str) -> Option<(usize, Vec<Cow<'p, str>>)> {
+        tree.find_where(path, |_| true)
+            .map(|m| (*m.value, m.params.into_iter().map(|p| p.value).collect()))
+    }
+
+    #[test]
+    fn static_beats_param_beats_wildcard() {
+        // added in the worst order on purpose
+        let tree = tree(&[
+            "/user/{*rest}",
+            "/user/{name}",
+            "/user/{id:u64}",
+            "/user/me",
+        ]);
+        assert_eq!(find(&tree, "/user/me"), Some((3, vec![])));
+        assert_eq!(find(&tree, "/user/42"), Some((2, vec!["42".into()])));
+        assert_eq!(find(&tree, "/user/bob"), Some((1, vec!["bob".into()])));
+        assert_eq!(
+            find(&tree, "/user/bob/posts"),
+            Some((0, vec!["bob/posts".into()]))
+        );
+    }
+
+    #[test]
+    fn typed_params_are_parsed() {
+        let tree = tree(&["/n/{id:u64}"]);
+        let found = tree.find_where("/n/42", |_| true).unwrap();
+        assert_eq!(found.params[0].parsed, Some(ParamValue::Uint(42)));
+        assert!(tree.find_where("/n/-1", |_| true).is_none());
+    }
+
+    #[test]
+    fn backtracks_out_of_dead_ends() {
+        let tree = tree(&["/a/bc/d", "/a/{x}/e", "/a/{*rest}"]);
+        // /a/bc matches the static child, but only /d comes after it
+        assert_eq!(find(&tree, "/a/bc/e"), Some((1, vec!["bc".into()])));
+        assert_eq!(find(&tree, "/a/bc/f"), Some((2, vec!["bc/f".into()])));
+        assert_eq!(find(&tree, "/a/bc/d"), Some((0, vec![])));
+    }
+
+    #[test]
+    fn splits_nodes() {
+        let tree = tree(&["/abc", "/abd", "/a", "/ab/{x}"]);
+        assert_eq!(find(&tree, "/abc"), Some((0, vec![])));
+        assert_eq!(find(&tree, "/abd"), Some((1, vec![])));
+        assert_eq!(find(&tree, "/a"), Some((2, vec![])));
+        assert_eq!(find(&tree, "/ab/c"), Some((3, vec!["c".into()])));
+        assert_eq!(find(&tree, "/ab"), None);
+        assert_eq!(find(&tree, "/abcd"), None);
+    }
+
+    #[test]
+    fn splits_inside_multibyte_chars() {
+        // é and è share their first byte, so the node splits in the middle of the char
+        let tree = tree(&["/é", "/è", "/é/{x}", "/日本/{*rest}"]);
+        assert_eq!(find(&tree, "/é"), Some((0, vec![])));
+        assert_eq!(find(&tree, "/è"), Some((1, vec![])));
+        assert_eq!(find(&tree, "/é/ü"), Some((2, vec!["ü".into()])));
+        assert_eq!(find(&tree, "/日本/語/x"), Some((3, vec!["語/x".into()])));
+        assert_eq!(find(&tree, "/ê"), None);
+        assert_eq!(find(&tree, "/日"), None);
+    }
+
+    #[test]
+    fn params_in_order() {
+        let tree = tree(&["/{a}/x/{b}", "/{a}/"]);
+        assert_eq!(
+            find(&tree, "/1/x/2"),
+            Some((0, vec!["1".into(), "2".into()]))
+        );
+        // a param can be empty
+        assert_eq!(find(&tree, "//x/2"), Some((0, vec!["".into(), "2".into()])));
+        assert_eq!(find(&tree, "/1/"), Some((1, vec!["1".into()])));
+    }
+
+    #[test]
+    fn same_paths_share_a_value() {
+        let mut tree = tree(&["/a/{x}"]);
+        assert_eq!(*tree.get_or_insert_with("/a/{y}", || 1), 0);
+        assert_eq!(*tree.get_or_insert_with("/a/{y:u64}", || 2), 2);
+        // aliases are the same constraint
+        assert_eq!(*tree.get_or_insert_with("/a/{z:uint}", || 3), 2);
+        assert_eq!(*tree.get_or_insert_with("/a/{z:[0-9]+}", || 4), 4);
+        assert_eq!(*tree.get_or_insert_with("/a/{z:\\d+}", || 5), 4);
+    }
+
+    #[test]
+    fn constraint_priority_ignores_order() {
+        for patterns in [
+            ["/c/{a:[0-9a-f]+}", "/c/{b:u64}", "/c/{c:u8}", "/c/{d:f64}"],
+            ["/c/{d:f64}", "/c/{c:u8}", "/c/{b:u64}", "/c/{a:[0-9a-f]+}"],
+        ] {
+            let tree = tree(&patterns);
+            let index = |p: &str| patterns.iter().position(|x| *x == p);
+            let found = |path| find(&tree, path).map(|(i, _)| i);
+            // narrower ints first, then wider ones, floats and regexes last
+            assert_eq!(found("/c/5"), index("/c/{c:u8}"));
+            assert_eq!(found("/c/500"), index("/c/{b:u64}"));
+            assert_eq!(found("/c/1.5"), index("/c/{d:f64}"));
+            assert_eq!(found("/c/99999999999999999999999"), index("/c/{d:f64}"));
+            assert_eq!(found("/c/ff"), index("/c/{a:[0-9a-f]+}"));
+        }
+    }
+
+    #[test]
+    fn find_where_falls_through() {
+        let tree = tree(&["/a/b", "/a/{x}", "/a/{*rest}"]);
+        let found = tree.find_where("/a/b", |v| *v != 0).unwrap();
+        assert_eq!((*found.value, found.params[0].value.as_ref()), (1, "b"));
+        let found = tree.find_where("/a/b", |v| *v == 2).unwrap();
+        assert_eq!((*found.value, found.params[0].value.as_ref()), (2, "b"));
+        assert!(tree.find_where("/a/b", |_| false).is_none());
+    }
+
+    #[test]
+    fn params_are_decoded_but_the_wildcard_isnt() {
+        let tree = tree(&["/n/{id:u64}", "/p/{name}", "/w/{*rest}"]);
+        // the constraint sees the decoded value
+        assert_eq!(find(&tree, "/n/%34%32"), Some((0, vec!["42".into()])));
+        assert_eq!(find(&tree, "/p/a%20b%2Fc"), Some((1, vec!["a b/c".into()])));
+        assert_eq!(find(&tree, "/p/a+b"), Some((1, vec!["a+b".into()])));
+        assert_eq!(find(&tree, "/w/a%20b/c"), Some((2, vec!["a%20b/c".into()])));
+    }
+}